@@ -0,0 +1,69 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use shambler::{
+    face::{FaceId, FaceIndices, FaceVertices},
+    line::{line_face_connections, line_face_connections_exhaustive, line_faces, lines},
+    Vector3,
+};
+
+/// Square grid of quads on several planes, roughly `4 * size * size` lines per plane
+fn quad_grid(size: usize) -> (FaceVertices, FaceIndices) {
+    let mut face_vertices = FaceVertices::default();
+    let mut face_indices = FaceIndices::default();
+
+    let rotations = [
+        nalgebra::Rotation3::identity(),
+        nalgebra::Rotation3::from_euler_angles(std::f32::consts::FRAC_PI_2, 0.0, 0.0),
+        nalgebra::Rotation3::from_euler_angles(0.3, 0.7, 1.1),
+    ];
+
+    for rotation in rotations.iter() {
+        for x in 0..size {
+            for y in 0..size {
+                let (x, y) = (x as f32 * 16.0, y as f32 * 16.0);
+                let corners: [Vector3; 4] = [
+                    nalgebra::vector![x, y, 0.0],
+                    nalgebra::vector![x + 16.0, y, 0.0],
+                    nalgebra::vector![x + 16.0, y + 16.0, 0.0],
+                    nalgebra::vector![x, y + 16.0, 0.0],
+                ];
+
+                let face_id = FaceId(face_vertices.len());
+                face_vertices.insert(face_id, corners.iter().map(|c| rotation * c).collect());
+                face_indices.insert(face_id, vec![0, 1, 2, 3]);
+            }
+        }
+    }
+
+    (face_vertices, face_indices)
+}
+
+fn bench_line_face_connections(c: &mut Criterion) {
+    let mut group = c.benchmark_group("line_face_connections");
+    group.sample_size(10);
+
+    for size in [9, 18, 36, 91, 183] {
+        let (face_vertices, face_indices) = quad_grid(size);
+        let (lines, face_lines) = lines(&face_indices);
+        let line_faces = line_faces(&face_lines);
+
+        group.bench_with_input(BenchmarkId::new("bucketed", lines.len()), &size, |b, _| {
+            b.iter(|| line_face_connections(&lines, &line_faces, &face_vertices))
+        });
+
+        // Quadratic, so only measured at small sizes
+        if size <= 36 {
+            group.bench_with_input(
+                BenchmarkId::new("exhaustive", lines.len()),
+                &size,
+                |b, _| {
+                    b.iter(|| line_face_connections_exhaustive(&lines, &line_faces, &face_vertices))
+                },
+            );
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_line_face_connections);
+criterion_main!(benches);
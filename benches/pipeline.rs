@@ -0,0 +1,125 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use shambler::{
+    brush::brush_hulls,
+    face::{
+        face_centers, face_duplicates, face_indices, face_planes, face_triangle_indices,
+        face_vertices, interior_faces, normals_flat, FaceWinding, NormalMode, Triangulation,
+    },
+    generate,
+    line::{line_duplicates, line_face_connections, line_faces, lines, manifold_lines},
+    shalrath::repr::Map,
+    texture::TextureSizes,
+    GeoMap, GeoTables,
+};
+
+/// Generated maps of a size where the quadratic stages still finish in reasonable time
+fn fixtures() -> Vec<(&'static str, Map)> {
+    vec![
+        ("box_grid", generate::box_grid([8, 8, 2], 64.0, 0.0)),
+        ("cylinders", generate::cylinders(32, 24)),
+        ("staircase", generate::staircase(128)),
+        ("overlapping_boxes", generate::overlapping_boxes(128, 0)),
+        ("large_world", generate::large_world(6)),
+    ]
+}
+
+fn bench_pipeline(c: &mut Criterion) {
+    for (name, map) in fixtures() {
+        let mut group = c.benchmark_group(name);
+        group.sample_size(10);
+
+        group.bench_function("geo_map", |b| b.iter(|| GeoMap::new(map.clone())));
+        let geo_map = GeoMap::new(map);
+
+        group.bench_function("face_planes", |b| {
+            b.iter(|| face_planes(&geo_map.face_planes))
+        });
+        let planes = face_planes(&geo_map.face_planes);
+
+        group.bench_function("brush_hulls", |b| {
+            b.iter(|| brush_hulls(&geo_map.brush_faces, &planes))
+        });
+        let hulls = brush_hulls(&geo_map.brush_faces, &planes);
+
+        group.bench_function("face_vertices", |b| {
+            b.iter(|| face_vertices(&geo_map.brush_faces, &planes, &hulls, &()))
+        });
+        let (vertices, _) = face_vertices(&geo_map.brush_faces, &planes, &hulls, &());
+
+        group.bench_function("face_centers", |b| b.iter(|| face_centers(&vertices)));
+        let centers = face_centers(&vertices);
+
+        group.bench_function("face_indices", |b| {
+            b.iter(|| face_indices(&planes, &vertices, &centers, FaceWinding::Clockwise))
+        });
+        let indices = face_indices(&planes, &vertices, &centers, FaceWinding::Clockwise);
+
+        group.bench_function("face_triangle_indices", |b| {
+            b.iter(|| face_triangle_indices(&indices))
+        });
+
+        group.bench_function("normals_flat", |b| {
+            b.iter(|| normals_flat(&vertices, &planes))
+        });
+        let normals = normals_flat(&vertices, &planes);
+
+        group.bench_function("lines", |b| b.iter(|| lines(&indices)));
+        let (lines, face_lines) = lines(&indices);
+        let line_faces = line_faces(&face_lines);
+
+        group.bench_function("line_face_connections", |b| {
+            b.iter(|| line_face_connections(&lines, &line_faces, &vertices))
+        });
+        let connections = line_face_connections(&lines, &line_faces, &vertices);
+        let (_, non_manifold_lines) = manifold_lines(&connections);
+
+        group.bench_function("face_duplicates", |b| {
+            b.iter(|| face_duplicates(&geo_map.faces, &planes, &vertices))
+        });
+        let duplicates = face_duplicates(&geo_map.faces, &planes, &vertices);
+
+        group.bench_function("line_duplicates", |b| {
+            b.iter(|| {
+                line_duplicates(
+                    &geo_map.brushes,
+                    &lines,
+                    &geo_map.brush_faces,
+                    &duplicates,
+                    &vertices,
+                    &face_lines,
+                )
+            })
+        });
+
+        group.bench_function("interior_faces", |b| {
+            b.iter(|| {
+                interior_faces(
+                    &geo_map.faces,
+                    &face_lines,
+                    &normals,
+                    &centers,
+                    &non_manifold_lines,
+                    &connections,
+                )
+            })
+        });
+
+        group.bench_function("geo_tables", |b| {
+            b.iter(|| {
+                GeoTables::new(
+                    &geo_map,
+                    &TextureSizes::default(),
+                    FaceWinding::Clockwise,
+                    Triangulation::Fan,
+                    NormalMode::Flat,
+                    &(),
+                )
+            })
+        });
+
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_pipeline);
+criterion_main!(benches);
@@ -0,0 +1,37 @@
+use std::collections::BTreeMap;
+
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use usage::Usage;
+
+use super::BrushId;
+use crate::{
+    face::{FaceId, FaceVertices},
+    Vector3,
+};
+
+pub enum BrushBoundsTag {}
+
+/// Axis-aligned bounds of each brush as `(min, max)`, omitting brushes without vertices
+pub type BrushBounds = Usage<BrushBoundsTag, BTreeMap<BrushId, (Vector3, Vector3)>>;
+
+// Calculate brush bounds from face vertices
+pub fn brush_bounds(
+    brush_faces: &BTreeMap<BrushId, Vec<FaceId>>,
+    face_vertices: &FaceVertices,
+) -> BrushBounds {
+    brush_faces
+        .par_iter()
+        .flat_map(|(brush_id, face_ids)| {
+            let mut vertices = face_ids
+                .iter()
+                .flat_map(|face_id| &face_vertices[face_id]);
+
+            let first = *vertices.next()?;
+            let bounds = vertices.fold((first, first), |(min, max), vertex| {
+                (min.inf(vertex), max.sup(vertex))
+            });
+
+            Some((*brush_id, bounds))
+        })
+        .collect()
+}
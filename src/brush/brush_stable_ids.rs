@@ -0,0 +1,112 @@
+use std::collections::BTreeMap;
+
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use usage::{AsUsage, Usage};
+
+use super::BrushId;
+use crate::{face::FaceId, stable_id::StableHasher, FacePlanes, StableId};
+
+pub enum BrushStableIdsTag {}
+pub enum StableBrushesTag {}
+
+pub type BrushStableIds = Usage<BrushStableIdsTag, BTreeMap<BrushId, StableId>>;
+
+/// Lookup from [`StableId`] to the current [`BrushId`]
+pub type StableBrushes = Usage<StableBrushesTag, BTreeMap<StableId, BrushId>>;
+
+/// Identify brushes by a hash of their plane set
+///
+/// Independent of face order and texturing, so the ID is preserved
+/// until the brush itself is reshaped or moved.
+pub fn brush_stable_ids(
+    brush_faces: &BTreeMap<BrushId, Vec<FaceId>>,
+    face_planes: &FacePlanes,
+) -> BrushStableIds {
+    let hashes = brush_faces
+        .par_iter()
+        .map(|(brush_id, face_ids)| {
+            let mut planes = face_ids
+                .iter()
+                .map(|face_id| face_planes[face_id].quantized())
+                .collect::<Vec<_>>();
+            planes.sort_unstable();
+            planes.dedup();
+
+            let mut hasher = StableHasher::default();
+            for plane in planes {
+                for component in plane {
+                    hasher.write_i64(component);
+                }
+            }
+
+            (*brush_id, hasher.finish())
+        })
+        .collect::<Vec<_>>();
+
+    BrushStableIdsTag::as_usage(crate::stable_id::stable_ids(hashes))
+}
+
+pub fn stable_brushes(brush_stable_ids: &BrushStableIds) -> StableBrushes {
+    brush_stable_ids
+        .iter()
+        .map(|(brush_id, stable_id)| (*stable_id, *brush_id))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        face::{face_planes, face_stable_ids, FaceStableIds},
+        generate::{cube, worldspawn},
+        GeoMap,
+    };
+
+    fn stable_ids(brushes: Vec<shalrath::repr::Brush>) -> (BrushStableIds, FaceStableIds) {
+        let geo_map = GeoMap::new(worldspawn(brushes));
+        let planes = face_planes(&geo_map.face_planes);
+
+        let brush_ids = brush_stable_ids(&geo_map.brush_faces, &planes);
+        let face_ids = face_stable_ids(&geo_map.brush_faces, &brush_ids, &planes);
+        (brush_ids, face_ids)
+    }
+
+    #[test]
+    fn test_brush_stable_ids() {
+        let (old_brushes, old_faces) = stable_ids(vec![cube(0.0), cube(64.0), cube(128.0)]);
+
+        // Moving a brush only changes the IDs of that brush and its faces
+        let (moved_brushes, moved_faces) = stable_ids(vec![cube(0.0), cube(80.0), cube(128.0)]);
+        for brush_id in [BrushId(0), BrushId(2)] {
+            assert_eq!(moved_brushes[&brush_id], old_brushes[&brush_id]);
+        }
+        assert_ne!(moved_brushes[&BrushId(1)], old_brushes[&BrushId(1)]);
+
+        for face_id in (0..6).chain(12..18).map(FaceId) {
+            assert_eq!(moved_faces[&face_id], old_faces[&face_id]);
+        }
+        for face_id in (6..12).map(FaceId) {
+            assert_ne!(moved_faces[&face_id], old_faces[&face_id]);
+        }
+
+        // Retexturing, reordering faces and reordering brushes preserve every ID
+        let mut retextured = cube(64.0);
+        retextured.0[0].texture = "sky".to_string();
+        retextured.0.reverse();
+
+        let (reordered_brushes, reordered_faces) =
+            stable_ids(vec![cube(128.0), retextured, cube(0.0)]);
+
+        let brushes = |ids: &BrushStableIds| ids.values().copied().collect::<Vec<_>>();
+        let faces = |ids: &FaceStableIds| {
+            let mut ids = ids.values().copied().collect::<Vec<_>>();
+            ids.sort_unstable();
+            ids
+        };
+
+        let mut expected = brushes(&old_brushes);
+        expected.reverse();
+        assert_eq!(brushes(&reordered_brushes), expected);
+        assert_eq!(faces(&reordered_faces), faces(&old_faces));
+    }
+}
@@ -1,11 +1,17 @@
+mod brush_bounds;
 mod brush_centers;
 mod brush_entities;
 mod brush_face_containment;
 mod brush_hulls;
 mod brush_id;
+mod brush_stable_ids;
+mod origin_brushes;
 
+pub use brush_bounds::*;
 pub use brush_centers::*;
 pub use brush_entities::*;
 pub use brush_face_containment::*;
 pub use brush_hulls::*;
 pub use brush_id::*;
+pub use brush_stable_ids::*;
+pub use origin_brushes::*;
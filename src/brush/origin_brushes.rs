@@ -0,0 +1,139 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use usage::Usage;
+
+use super::BrushId;
+use crate::{
+    entity::{entity_property, EntityId},
+    face::FaceId,
+    texture::{TextureClass, TextureClasses, TextureId},
+    EntityBrushes, EntityProperties,
+};
+
+pub enum OriginBrushesTag {}
+
+/// The set of brush entity brushes textured entirely with `origin`
+pub type OriginBrushes = Usage<OriginBrushesTag, BTreeSet<BrushId>>;
+
+/// Find origin brushes, ignoring any in worldspawn
+///
+/// Worldspawn doesn't rotate, so an origin brush there is ordinary geometry
+/// rather than a pivot.
+pub fn origin_brushes(
+    entity_properties: &EntityProperties,
+    entity_brushes: &BTreeMap<EntityId, Vec<BrushId>>,
+    brush_faces: &BTreeMap<BrushId, Vec<FaceId>>,
+    face_textures: &BTreeMap<FaceId, TextureId>,
+    texture_classes: &TextureClasses,
+) -> OriginBrushes {
+    entity_brushes
+        .par_iter()
+        .filter(|(entity_id, _)| {
+            entity_property(&entity_properties[entity_id], "classname") != Some("worldspawn")
+        })
+        .flat_map(|(_, brush_ids)| {
+            brush_ids
+                .iter()
+                .filter(|brush_id| {
+                    brush_faces[brush_id].iter().all(|face_id| {
+                        texture_classes[&face_textures[face_id]] == TextureClass::Origin
+                    })
+                })
+                .copied()
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Copy entity brushes, omitting origin brushes so they are excluded from render geometry
+pub fn strip_origin_brushes(
+    entity_brushes: &BTreeMap<EntityId, Vec<BrushId>>,
+    origin_brushes: &OriginBrushes,
+) -> EntityBrushes {
+    entity_brushes
+        .par_iter()
+        .map(|(entity_id, brush_ids)| {
+            (
+                *entity_id,
+                brush_ids
+                    .iter()
+                    .filter(|brush_id| !origin_brushes.contains(brush_id))
+                    .copied()
+                    .collect(),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        brush::brush_centers,
+        entity::entity_pivots,
+        generate::{box_brush, entity, pipeline, Pipeline},
+        texture::texture_classes,
+    };
+    use shalrath::repr::Map;
+
+    fn origin_box(min: f32, max: f32) -> shalrath::repr::Brush {
+        let mut brush = box_brush(
+            nalgebra::vector![min, min, min],
+            nalgebra::vector![max, max, max],
+        );
+        for plane in brush.0.iter_mut() {
+            plane.texture = "origin".to_string();
+        }
+        brush
+    }
+
+    #[test]
+    fn test_origin_brushes() {
+        let solid = || {
+            box_brush(
+                nalgebra::vector![0.0, 0.0, 0.0],
+                nalgebra::vector![64.0, 64.0, 64.0],
+            )
+        };
+
+        let Pipeline {
+            geo_map, centers, ..
+        } = pipeline(Map(vec![
+            entity(
+                &[("classname", "worldspawn")],
+                vec![solid(), origin_box(-8.0, 8.0)],
+            ),
+            entity(
+                &[("classname", "func_rotating")],
+                vec![solid(), origin_box(24.0, 40.0)],
+            ),
+            entity(&[("classname", "func_door")], vec![solid()]),
+        ]));
+
+        let texture_classes = texture_classes(&geo_map.textures);
+        let origin_brushes = origin_brushes(
+            &geo_map.entity_properties,
+            &geo_map.entity_brushes,
+            &geo_map.brush_faces,
+            &geo_map.face_textures,
+            &texture_classes,
+        );
+
+        // Only the rotating entity's origin brush, not worldspawn's
+        assert_eq!(
+            *origin_brushes,
+            std::iter::once(BrushId(3)).collect::<BTreeSet<_>>()
+        );
+
+        let stripped = strip_origin_brushes(&geo_map.entity_brushes, &origin_brushes);
+        assert_eq!(stripped[&EntityId(0)], vec![BrushId(0), BrushId(1)]);
+        assert_eq!(stripped[&EntityId(1)], vec![BrushId(2)]);
+
+        let centers = brush_centers(&geo_map.brush_faces, &centers);
+
+        let pivots = entity_pivots(&geo_map.entity_brushes, &origin_brushes, &centers);
+        assert_eq!(pivots.len(), 1);
+        assert!((pivots[&EntityId(1)] - nalgebra::vector![32.0, 32.0, 32.0]).magnitude() < 0.01);
+    }
+}
@@ -0,0 +1,228 @@
+//! Versioned binary cache for processed maps
+//!
+//! A cache file consists of a fixed header followed by a bincode payload:
+//! the magic bytes [`CACHE_MAGIC`], a little-endian `u32` [`CACHE_VERSION`],
+//! and a little-endian `u64` hash of the source the payload was built from.
+use std::{
+    fmt::Display,
+    io::{Read, Write},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::stable_id::StableHasher;
+
+pub const CACHE_MAGIC: [u8; 4] = *b"SHMB";
+
+/// Bumped whenever the layout of any cached type changes
+pub const CACHE_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum CacheError {
+    Io(std::io::Error),
+    /// The data does not start with [`CACHE_MAGIC`]
+    NotACache,
+    /// The cache was written by an incompatible version of this crate
+    Version {
+        found: u32,
+    },
+    /// The cache was built from a different source
+    Stale,
+    Encoding(bincode::Error),
+}
+
+impl Display for CacheError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CacheError::Io(error) => write!(f, "Cache I/O error: {}", error),
+            CacheError::NotACache => write!(f, "Data is not a map cache"),
+            CacheError::Version { found } => write!(
+                f,
+                "Cache version {} does not match expected version {}",
+                found, CACHE_VERSION
+            ),
+            CacheError::Stale => write!(f, "Cache was built from a different source"),
+            CacheError::Encoding(error) => write!(f, "Cache encoding error: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for CacheError {}
+
+impl From<std::io::Error> for CacheError {
+    fn from(error: std::io::Error) -> Self {
+        CacheError::Io(error)
+    }
+}
+
+impl From<bincode::Error> for CacheError {
+    fn from(error: bincode::Error) -> Self {
+        CacheError::Encoding(error)
+    }
+}
+
+/// Hash source data, such as the bytes of a `.map` file, for cache validation
+pub fn cache_source_hash(source: &[u8]) -> u64 {
+    let mut hasher = StableHasher::default();
+    hasher.write(source);
+    hasher.finish()
+}
+
+/// Write `value` to a cache tagged with the hash of its source
+pub fn write_cache<T: Serialize>(
+    mut writer: impl Write,
+    source_hash: u64,
+    value: &T,
+) -> Result<(), CacheError> {
+    writer.write_all(&CACHE_MAGIC)?;
+    writer.write_all(&CACHE_VERSION.to_le_bytes())?;
+    writer.write_all(&source_hash.to_le_bytes())?;
+    bincode::serialize_into(writer, value)?;
+    Ok(())
+}
+
+/// Read a value from a cache, failing if it was written by another version or from another source
+pub fn read_cache<T: DeserializeOwned>(
+    mut reader: impl Read,
+    source_hash: u64,
+) -> Result<T, CacheError> {
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if magic != CACHE_MAGIC {
+        return Err(CacheError::NotACache);
+    }
+
+    let mut version = [0; 4];
+    reader.read_exact(&mut version)?;
+    let version = u32::from_le_bytes(version);
+    if version != CACHE_VERSION {
+        return Err(CacheError::Version { found: version });
+    }
+
+    let mut hash = [0; 8];
+    reader.read_exact(&mut hash)?;
+    if u64::from_le_bytes(hash) != source_hash {
+        return Err(CacheError::Stale);
+    }
+
+    Ok(bincode::deserialize_from(reader)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        face::FaceId,
+        generate::{box_brush, cylinder_brush, entity},
+        GeoMap, Plane3d, Vector3,
+    };
+    use shalrath::repr::{Extension, Map, Property, TextureOffset, TexturePlane};
+
+    #[test]
+    fn test_cache_round_trip() {
+        let value = (
+            FaceId(3),
+            Plane3d {
+                n: Vector3::z(),
+                d: 16.0,
+            },
+        );
+
+        let mut bytes = vec![];
+        write_cache(&mut bytes, 7, &value).unwrap();
+
+        let read: (FaceId, Plane3d) = read_cache(bytes.as_slice(), 7).unwrap();
+        assert_eq!(read, value);
+
+        assert!(matches!(
+            read_cache::<(FaceId, Plane3d)>(bytes.as_slice(), 8),
+            Err(CacheError::Stale)
+        ));
+
+        bytes[4] = bytes[4].wrapping_add(1);
+        assert!(matches!(
+            read_cache::<(FaceId, Plane3d)>(bytes.as_slice(), 7),
+            Err(CacheError::Version { .. })
+        ));
+    }
+
+    #[test]
+    fn test_geo_map_round_trip() {
+        let mut valve = box_brush(
+            nalgebra::vector![64.0, 0.0, 0.0],
+            nalgebra::vector![96.0, 32.0, 32.0],
+        );
+        valve.0[0].texture_offset = TextureOffset::Valve {
+            u: TexturePlane {
+                x: 0.0,
+                y: 1.0,
+                z: 0.0,
+                d: 8.0,
+            },
+            v: TexturePlane {
+                x: 0.0,
+                y: 0.0,
+                z: -1.0,
+                d: -4.0,
+            },
+        };
+        valve.0[1].angle = 45.0;
+        valve.0[1].scale_x = 0.5;
+        valve.0[2].extension = Extension::Quake2 {
+            content_flags: 1,
+            surface_flags: 4,
+            value: 200.0,
+        };
+
+        let geo_map = GeoMap::new(Map(vec![
+            entity(
+                &[("classname", "worldspawn"), ("message", "round trip")],
+                vec![
+                    box_brush(Vector3::zeros(), nalgebra::vector![32.0, 32.0, 32.0]),
+                    valve,
+                ],
+            ),
+            entity(
+                &[("classname", "func_detail")],
+                vec![cylinder_brush(Vector3::zeros(), 16.0, 64.0, 8)],
+            ),
+            entity(&[("classname", "light"), ("origin", "0 0 64")], vec![]),
+        ]));
+
+        let mut bytes = vec![];
+        write_cache(&mut bytes, 0, &geo_map).unwrap();
+        let read: GeoMap = read_cache(bytes.as_slice(), 0).unwrap();
+
+        let properties = |geo_map: &GeoMap| {
+            geo_map
+                .entity_properties
+                .iter()
+                .map(|(entity_id, properties)| {
+                    (
+                        *entity_id,
+                        properties
+                            .0
+                            .iter()
+                            .map(|Property { key, value }| (key.clone(), value.clone()))
+                            .collect::<Vec<_>>(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(*read.entities, *geo_map.entities);
+        assert_eq!(*read.brushes, *geo_map.brushes);
+        assert_eq!(*read.faces, *geo_map.faces);
+        assert_eq!(*read.textures, *geo_map.textures);
+        assert_eq!(properties(&read), properties(&geo_map));
+        assert_eq!(*read.entity_brushes, *geo_map.entity_brushes);
+        assert_eq!(*read.point_entities, *geo_map.point_entities);
+        assert_eq!(*read.brush_faces, *geo_map.brush_faces);
+        assert_eq!(*read.face_planes, *geo_map.face_planes);
+        assert_eq!(*read.face_textures, *geo_map.face_textures);
+        assert_eq!(*read.face_offsets, *geo_map.face_offsets);
+        assert_eq!(*read.face_angles, *geo_map.face_angles);
+        assert_eq!(*read.face_scales, *geo_map.face_scales);
+        assert_eq!(*read.face_extensions, *geo_map.face_extensions);
+    }
+}
@@ -1,18 +1,32 @@
-use crate::{Plane3d, Vector3, EPSILON};
+use crate::{face::triplanar_intersection, Plane3d, Vector3, EPSILON};
 
 /// A convex hull described by a set of planes
 #[derive(Debug, Clone)]
-pub struct ConvexHull(Vec<Plane3d>);
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConvexHull {
+    planes: Vec<Plane3d>,
+    bounds: Option<(Vector3, Vector3)>,
+}
 
 impl<'a, T: IntoIterator<Item = Plane3d>> From<T> for ConvexHull {
     fn from(planes: T) -> Self {
-        ConvexHull(planes.into_iter().collect())
+        let mut hull = ConvexHull {
+            planes: planes.into_iter().collect(),
+            bounds: None,
+        };
+        hull.bounds = hull.vertex_bounds();
+        hull
     }
 }
 
 impl ConvexHull {
+    /// Axis-aligned bounds as `(min, max)`, or `None` if the planes enclose no vertices
+    pub fn bounds(&self) -> Option<(Vector3, Vector3)> {
+        self.bounds
+    }
+
     pub fn contains(&self, vertex: &Vector3) -> bool {
-        for plane in &self.0 {
+        for plane in &self.planes {
             let proj = plane.normal().dot(vertex);
             if proj > plane.distance() && (proj - plane.distance()).abs() > EPSILON {
                 return false;
@@ -20,5 +34,182 @@ impl ConvexHull {
         }
         true
     }
+
+    /// Returns the distance along `direction` at which a ray enters the hull,
+    /// or `None` if it misses or only enters beyond `max_distance`
+    ///
+    /// `direction` is expected to be normalized.
+    /// Rays starting inside the hull report an entry distance of zero.
+    /// Segments that miss the hull's bounds are rejected before testing its planes.
+    pub fn ray_intersection(
+        &self,
+        origin: &Vector3,
+        direction: &Vector3,
+        max_distance: f32,
+    ) -> Option<f32> {
+        if let Some(bounds) = &self.bounds {
+            if !segment_hits_bounds(bounds, origin, direction, max_distance) {
+                return None;
+            }
+        }
+
+        let mut enter = 0.0f32;
+        let mut exit = max_distance;
+
+        for plane in &self.planes {
+            let denom = plane.normal().dot(direction);
+            let dist = plane.normal().dot(origin) - plane.distance();
+
+            if denom.abs() < EPSILON {
+                // Parallel to this plane; miss if outside it
+                if dist > EPSILON {
+                    return None;
+                }
+                continue;
+            }
+
+            let t = -dist / denom;
+            if denom < 0.0 {
+                enter = enter.max(t);
+            } else {
+                exit = exit.min(t);
+            }
+
+            if exit - enter <= EPSILON {
+                return None;
+            }
+        }
+
+        Some(enter)
+    }
+
+    // Bounds of the three-plane intersections that lie within the hull
+    fn vertex_bounds(&self) -> Option<(Vector3, Vector3)> {
+        let planes = &self.planes;
+
+        let mut vertices = (0..planes.len())
+            .flat_map(|i| {
+                (i + 1..planes.len()).flat_map(move |j| {
+                    (j + 1..planes.len()).flat_map(move |k| {
+                        triplanar_intersection(&planes[i], &planes[j], &planes[k])
+                            .or_else(|| triplanar_intersection(&planes[i], &planes[k], &planes[j]))
+                    })
+                })
+            })
+            .filter(|vertex| self.contains(vertex));
+
+        let first = vertices.next()?;
+        Some(vertices.fold((first, first), |(min, max), vertex| {
+            (min.inf(&vertex), max.sup(&vertex))
+        }))
+    }
 }
 
+/// Slab test between a segment and a padded axis-aligned box
+fn segment_hits_bounds(
+    (min, max): &(Vector3, Vector3),
+    origin: &Vector3,
+    direction: &Vector3,
+    distance: f32,
+) -> bool {
+    const PADDING: f32 = 0.01;
+
+    let mut enter = 0.0f32;
+    let mut exit = distance;
+
+    for i in 0..3 {
+        let (min, max) = (min[i] - PADDING, max[i] + PADDING);
+
+        if direction[i].abs() <= f32::EPSILON {
+            if origin[i] < min || origin[i] > max {
+                return false;
+            }
+            continue;
+        }
+
+        let t0 = (min - origin[i]) / direction[i];
+        let t1 = (max - origin[i]) / direction[i];
+        enter = enter.max(t0.min(t1));
+        exit = exit.min(t0.max(t1));
+
+        if enter > exit {
+            return false;
+        }
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Box spanning the origin to `size` along each axis
+    fn box_hull(size: f32) -> ConvexHull {
+        let mut planes = vec![];
+        for n in [Vector3::x(), Vector3::y(), Vector3::z()].iter() {
+            planes.push(Plane3d { n: *n, d: size });
+            planes.push(Plane3d { n: -n, d: 0.0 });
+        }
+        planes.into()
+    }
+
+    #[test]
+    fn test_bounds() {
+        let (min, max) = box_hull(64.0).bounds().unwrap();
+        assert!(min.magnitude() < EPSILON);
+        assert!((max - Vector3::repeat(64.0)).magnitude() < EPSILON);
+
+        // Opposing half-spaces with no overlap
+        let empty: ConvexHull = vec![
+            Plane3d {
+                n: Vector3::x(),
+                d: 0.0,
+            },
+            Plane3d {
+                n: -Vector3::x(),
+                d: -64.0,
+            },
+        ]
+        .into();
+        assert!(empty.bounds().is_none());
+    }
+
+    #[test]
+    fn test_ray_intersection() {
+        let hull = box_hull(64.0);
+        let x = Vector3::x();
+
+        let enter = hull.ray_intersection(&nalgebra::vector![-10.0, 32.0, 32.0], &x, 100.0);
+        assert!((enter.unwrap() - 10.0).abs() < EPSILON);
+
+        // Starting inside
+        assert_eq!(
+            hull.ray_intersection(&nalgebra::vector![32.0, 32.0, 32.0], &x, 100.0),
+            Some(0.0)
+        );
+
+        // Too short to reach the hull
+        assert_eq!(
+            hull.ray_intersection(&nalgebra::vector![-10.0, 32.0, 32.0], &x, 5.0),
+            None
+        );
+
+        // Pointing away
+        assert_eq!(
+            hull.ray_intersection(&nalgebra::vector![-10.0, 32.0, 32.0], &-x, 100.0),
+            None
+        );
+
+        // Parallel to and outside a pair of faces
+        assert_eq!(
+            hull.ray_intersection(&nalgebra::vector![-10.0, 100.0, 32.0], &x, 100.0),
+            None
+        );
+
+        // Diagonal entry through an edge region
+        let diagonal = nalgebra::vector![1.0, 1.0, 0.0].normalize();
+        let enter = hull.ray_intersection(&nalgebra::vector![-10.0, -10.0, 32.0], &diagonal, 100.0);
+        assert!((enter.unwrap() - 10.0 * 2.0f32.sqrt()).abs() < 0.01);
+    }
+}
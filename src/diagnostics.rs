@@ -0,0 +1,114 @@
+use std::{fmt::Display, sync::Mutex};
+
+use crate::{brush::BrushId, face::FaceId, texture::TextureId};
+
+/// A non-fatal problem encountered while processing a map
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Diagnostic {
+    /// A face references a texture with no known size, and was given the default
+    MissingTexture {
+        face_id: FaceId,
+        texture_id: TextureId,
+        texture: String,
+        default_size: (u32, u32),
+    },
+    /// A face produced fewer than three vertices
+    DegenerateFace {
+        brush_id: BrushId,
+        face_id: FaceId,
+        vertex_count: usize,
+    },
+    /// A brush produced no valid faces and will be absent from the output geometry
+    SkippedBrush { brush_id: BrushId },
+    /// A face's normals don't match its vertices one-to-one, so it was left unlit
+    MismatchedNormals {
+        face_id: FaceId,
+        vertex_count: usize,
+        normal_count: usize,
+    },
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Diagnostic::MissingTexture {
+                face_id,
+                texture,
+                default_size: (width, height),
+                ..
+            } => write!(
+                f,
+                "Texture {} not found for face {}, generating UV with default size of {}x{}",
+                texture, face_id, width, height
+            ),
+            Diagnostic::DegenerateFace {
+                brush_id,
+                face_id,
+                vertex_count,
+            } => write!(
+                f,
+                "Face {} of brush {} is degenerate with {} vertices",
+                face_id, brush_id, vertex_count
+            ),
+            Diagnostic::SkippedBrush { brush_id } => {
+                write!(f, "Brush {} has no valid faces and was skipped", brush_id)
+            }
+            Diagnostic::MismatchedNormals {
+                face_id,
+                vertex_count,
+                normal_count,
+            } => write!(
+                f,
+                "Face {} has {} vertices but {} normals and was left unlit",
+                face_id, vertex_count, normal_count
+            ),
+        }
+    }
+}
+
+/// Sink for diagnostics raised by the processing pipeline
+///
+/// Reported from inside rayon closures, so implementors must be [`Sync`].
+pub trait Diagnostics: Sync {
+    fn report(&self, diagnostic: Diagnostic);
+}
+
+/// Discard all diagnostics
+impl Diagnostics for () {
+    fn report(&self, _: Diagnostic) {}
+}
+
+/// Print diagnostics to stderr as warnings
+#[derive(Debug, Default, Copy, Clone)]
+pub struct PrintDiagnostics;
+
+impl Diagnostics for PrintDiagnostics {
+    fn report(&self, diagnostic: Diagnostic) {
+        eprintln!("Warning: {}", diagnostic);
+    }
+}
+
+/// Collect diagnostics for later inspection
+#[derive(Debug, Default)]
+pub struct DiagnosticCollector(Mutex<Vec<Diagnostic>>);
+
+impl DiagnosticCollector {
+    /// Returns a copy of the diagnostics collected so far
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.0.lock().unwrap().clone()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.lock().unwrap().is_empty()
+    }
+
+    pub fn into_inner(self) -> Vec<Diagnostic> {
+        self.0.into_inner().unwrap()
+    }
+}
+
+impl Diagnostics for DiagnosticCollector {
+    fn report(&self, diagnostic: Diagnostic) {
+        self.0.lock().unwrap().push(diagnostic);
+    }
+}
@@ -0,0 +1,634 @@
+//! Structural comparison of two [`GeoMap`]s
+//!
+//! Brushes are matched by their set of planes rather than by ID,
+//! so reordering brushes or entities in the source file does not register as a change.
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
+    fmt::Display,
+};
+
+use shalrath::repr::Property;
+
+use crate::{brush::BrushId, entity::EntityId, face::FaceId, GeoMap, Plane3d};
+
+/// A changed, added or removed entity property
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PropertyChange {
+    pub key: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+/// An entity present in both maps whose properties differ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntityChange {
+    pub old: EntityId,
+    pub new: EntityId,
+    pub properties: Vec<PropertyChange>,
+}
+
+/// A face present in both maps whose texture or alignment differs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FaceTextureChange {
+    pub old: FaceId,
+    pub new: FaceId,
+    pub old_texture: String,
+    pub new_texture: String,
+}
+
+/// Differences between two maps
+///
+/// IDs in `removed_*` and the `old` side of pairs refer to the old map,
+/// and IDs in `added_*` and the `new` side of pairs refer to the new map.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MapDiff {
+    /// Old to new entity IDs for every entity present in both maps
+    pub entity_matches: BTreeMap<EntityId, EntityId>,
+    /// Old to new brush IDs for every brush with identical geometry in both maps
+    pub brush_matches: BTreeMap<BrushId, BrushId>,
+
+    pub added_entities: Vec<EntityId>,
+    pub removed_entities: Vec<EntityId>,
+    pub modified_entities: Vec<EntityChange>,
+
+    pub added_brushes: Vec<BrushId>,
+    pub removed_brushes: Vec<BrushId>,
+    /// Brushes that share at least half of their planes, such as after a face was dragged
+    pub modified_brushes: Vec<(BrushId, BrushId)>,
+
+    pub retextured_faces: Vec<FaceTextureChange>,
+}
+
+impl MapDiff {
+    /// Returns true if the maps are equivalent
+    pub fn is_empty(&self) -> bool {
+        self.added_entities.is_empty()
+            && self.removed_entities.is_empty()
+            && self.modified_entities.is_empty()
+            && self.added_brushes.is_empty()
+            && self.removed_brushes.is_empty()
+            && self.modified_brushes.is_empty()
+            && self.retextured_faces.is_empty()
+    }
+}
+
+impl Display for MapDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for entity_id in &self.removed_entities {
+            writeln!(f, "- entity {}", entity_id)?;
+        }
+
+        for entity_id in &self.added_entities {
+            writeln!(f, "+ entity {}", entity_id)?;
+        }
+
+        for EntityChange {
+            old,
+            new,
+            properties,
+        } in &self.modified_entities
+        {
+            writeln!(f, "~ entity {} -> {}", old, new)?;
+            for PropertyChange { key, old, new } in properties {
+                match (old, new) {
+                    (Some(old), Some(new)) => writeln!(f, "    {}: {:?} -> {:?}", key, old, new)?,
+                    (Some(old), None) => writeln!(f, "  - {}: {:?}", key, old)?,
+                    (None, Some(new)) => writeln!(f, "  + {}: {:?}", key, new)?,
+                    (None, None) => (),
+                }
+            }
+        }
+
+        for brush_id in &self.removed_brushes {
+            writeln!(f, "- brush {}", brush_id)?;
+        }
+
+        for brush_id in &self.added_brushes {
+            writeln!(f, "+ brush {}", brush_id)?;
+        }
+
+        for (old, new) in &self.modified_brushes {
+            writeln!(f, "~ brush {} -> {}", old, new)?;
+        }
+
+        for FaceTextureChange {
+            old,
+            new,
+            old_texture,
+            new_texture,
+        } in &self.retextured_faces
+        {
+            writeln!(
+                f,
+                "~ face {} -> {}: {} -> {}",
+                old, new, old_texture, new_texture
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Compare two maps
+pub fn map_diff(old: &GeoMap, new: &GeoMap) -> MapDiff {
+    let old_keys = brush_plane_keys(old);
+    let new_keys = brush_plane_keys(new);
+
+    // Match brushes with identical plane sets, in ID order
+    let mut candidates = HashMap::<&BTreeSet<[i64; 4]>, VecDeque<BrushId>>::default();
+    for (brush_id, key) in &new_keys {
+        candidates.entry(key).or_default().push_back(*brush_id);
+    }
+
+    let brush_matches = old_keys
+        .iter()
+        .filter_map(|(old_id, key)| {
+            let new_id = candidates.get_mut(key)?.pop_front()?;
+            Some((*old_id, new_id))
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    // Pair up remaining brushes that share most of their planes
+    let matched_new = brush_matches.values().copied().collect::<BTreeSet<_>>();
+    let mut unmatched_new = new_keys
+        .keys()
+        .filter(|brush_id| !matched_new.contains(brush_id))
+        .copied()
+        .collect::<BTreeSet<_>>();
+
+    let mut modified_brushes = vec![];
+    let mut removed_brushes = vec![];
+    for (old_id, old_key) in &old_keys {
+        if brush_matches.contains_key(old_id) {
+            continue;
+        }
+
+        let best = unmatched_new
+            .iter()
+            .map(|new_id| {
+                let new_key = &new_keys[new_id];
+                (
+                    old_key.intersection(new_key).count(),
+                    old_key.len().max(new_key.len()),
+                    *new_id,
+                )
+            })
+            .filter(|(shared, total, _)| *shared > 0 && shared * 2 >= *total)
+            .max_by(|lhs, rhs| lhs.0.cmp(&rhs.0).then_with(|| rhs.2.cmp(&lhs.2)));
+
+        match best {
+            Some((_, _, new_id)) => {
+                unmatched_new.remove(&new_id);
+                modified_brushes.push((*old_id, new_id));
+            }
+            None => removed_brushes.push(*old_id),
+        }
+    }
+    let added_brushes = unmatched_new.into_iter().collect();
+
+    let entity_matches = entity_matches(old, new, &brush_matches, &modified_brushes);
+
+    let matched_new = entity_matches.values().copied().collect::<BTreeSet<_>>();
+    let removed_entities = old
+        .entities
+        .iter()
+        .filter(|entity_id| !entity_matches.contains_key(entity_id))
+        .copied()
+        .collect();
+    let added_entities = new
+        .entities
+        .iter()
+        .filter(|entity_id| !matched_new.contains(entity_id))
+        .copied()
+        .collect();
+
+    let modified_entities = entity_matches
+        .iter()
+        .filter_map(|(old_id, new_id)| {
+            let properties = property_changes(
+                &old.entity_properties[old_id].0,
+                &new.entity_properties[new_id].0,
+            );
+
+            if properties.is_empty() {
+                None
+            } else {
+                Some(EntityChange {
+                    old: *old_id,
+                    new: *new_id,
+                    properties,
+                })
+            }
+        })
+        .collect();
+
+    let retextured_faces = brush_matches
+        .iter()
+        .map(|(old_id, new_id)| (*old_id, *new_id))
+        .chain(modified_brushes.iter().copied())
+        .flat_map(|(old_id, new_id)| face_texture_changes(old, new, old_id, new_id))
+        .collect();
+
+    MapDiff {
+        entity_matches,
+        brush_matches,
+        added_entities,
+        removed_entities,
+        modified_entities,
+        added_brushes,
+        removed_brushes,
+        modified_brushes,
+        retextured_faces,
+    }
+}
+
+/// Order-independent geometric identity of each brush
+fn brush_plane_keys(map: &GeoMap) -> BTreeMap<BrushId, BTreeSet<[i64; 4]>> {
+    map.brush_faces
+        .iter()
+        .map(|(brush_id, face_ids)| {
+            (
+                *brush_id,
+                face_ids
+                    .iter()
+                    .map(|face_id| Plane3d::from(&map.face_planes[face_id]).quantized())
+                    .collect(),
+            )
+        })
+        .collect()
+}
+
+/// Match entities by shared brushes, then by identical properties, then by identifying keys
+fn entity_matches(
+    old: &GeoMap,
+    new: &GeoMap,
+    brush_matches: &BTreeMap<BrushId, BrushId>,
+    modified_brushes: &[(BrushId, BrushId)],
+) -> BTreeMap<EntityId, EntityId> {
+    let old_brush_entities = brush_entities(old);
+    let new_brush_entities = brush_entities(new);
+
+    let mut shared = BTreeMap::<(EntityId, EntityId), usize>::default();
+    for (old_brush, new_brush) in brush_matches
+        .iter()
+        .map(|(old_id, new_id)| (*old_id, *new_id))
+        .chain(modified_brushes.iter().copied())
+    {
+        *shared
+            .entry((
+                old_brush_entities[&old_brush],
+                new_brush_entities[&new_brush],
+            ))
+            .or_default() += 1;
+    }
+
+    let mut shared = shared.into_iter().collect::<Vec<_>>();
+    shared
+        .sort_by(|(lhs_ids, lhs), (rhs_ids, rhs)| rhs.cmp(lhs).then_with(|| lhs_ids.cmp(rhs_ids)));
+
+    let mut matches = BTreeMap::default();
+    let mut matched_new = BTreeSet::default();
+    for ((old_id, new_id), _) in shared {
+        if !matches.contains_key(&old_id) && !matched_new.contains(&new_id) {
+            matches.insert(old_id, new_id);
+            matched_new.insert(new_id);
+        }
+    }
+
+    let properties = |map: &GeoMap, entity_id: &EntityId| {
+        let mut properties = map.entity_properties[entity_id]
+            .0
+            .iter()
+            .map(|Property { key, value }| (key.clone(), value.clone()))
+            .collect::<Vec<_>>();
+        properties.sort();
+        Some(properties)
+    };
+
+    let property = |key: &'static str| {
+        move |map: &GeoMap, entity_id: &EntityId| {
+            let properties = &map.entity_properties[entity_id];
+            Some(vec![
+                (
+                    "classname".to_string(),
+                    crate::entity::entity_property(properties, "classname")?.to_string(),
+                ),
+                (
+                    key.to_string(),
+                    crate::entity::entity_property(properties, key)?.to_string(),
+                ),
+            ])
+        }
+    };
+
+    let classname = |map: &GeoMap, entity_id: &EntityId| {
+        let classname =
+            crate::entity::entity_property(&map.entity_properties[entity_id], "classname")?;
+        if classname == "worldspawn" {
+            Some(vec![("classname".to_string(), classname.to_string())])
+        } else {
+            None
+        }
+    };
+
+    match_remaining(old, new, &mut matches, properties);
+    match_remaining(old, new, &mut matches, property("targetname"));
+    match_remaining(old, new, &mut matches, property("origin"));
+    match_remaining(old, new, &mut matches, classname);
+
+    matches
+}
+
+/// Match unmatched entities with equal keys, in ID order
+fn match_remaining(
+    old: &GeoMap,
+    new: &GeoMap,
+    matches: &mut BTreeMap<EntityId, EntityId>,
+    key: impl Fn(&GeoMap, &EntityId) -> Option<Vec<(String, String)>>,
+) {
+    let matched_new = matches.values().copied().collect::<BTreeSet<_>>();
+
+    let mut candidates = HashMap::<Vec<(String, String)>, VecDeque<EntityId>>::default();
+    for entity_id in new.entities.iter().filter(|id| !matched_new.contains(id)) {
+        if let Some(key) = key(new, entity_id) {
+            candidates.entry(key).or_default().push_back(*entity_id);
+        }
+    }
+
+    for entity_id in old.entities.iter() {
+        if matches.contains_key(entity_id) {
+            continue;
+        }
+
+        if let Some(new_id) = key(old, entity_id)
+            .and_then(|key| candidates.get_mut(&key))
+            .and_then(VecDeque::pop_front)
+        {
+            matches.insert(*entity_id, new_id);
+        }
+    }
+}
+
+fn brush_entities(map: &GeoMap) -> BTreeMap<BrushId, EntityId> {
+    map.entity_brushes
+        .iter()
+        .flat_map(|(entity_id, brush_ids)| {
+            brush_ids
+                .iter()
+                .map(move |brush_id| (*brush_id, *entity_id))
+        })
+        .collect()
+}
+
+fn property_changes(old: &[Property], new: &[Property]) -> Vec<PropertyChange> {
+    let to_map = |properties: &[Property]| {
+        let mut map = BTreeMap::<String, String>::default();
+        for Property { key, value } in properties {
+            map.entry(key.clone()).or_insert_with(|| value.clone());
+        }
+        map
+    };
+
+    let old = to_map(old);
+    let new = to_map(new);
+
+    old.keys()
+        .chain(new.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter_map(|key| {
+            let old_value = old.get(key);
+            let new_value = new.get(key);
+            if old_value == new_value {
+                None
+            } else {
+                Some(PropertyChange {
+                    key: key.clone(),
+                    old: old_value.cloned(),
+                    new: new_value.cloned(),
+                })
+            }
+        })
+        .collect()
+}
+
+/// Compare texturing between faces of two matched brushes that lie on the same plane
+fn face_texture_changes(
+    old: &GeoMap,
+    new: &GeoMap,
+    old_brush: BrushId,
+    new_brush: BrushId,
+) -> Vec<FaceTextureChange> {
+    let new_faces = new.brush_faces[&new_brush]
+        .iter()
+        .map(|face_id| {
+            (
+                Plane3d::from(&new.face_planes[face_id]).quantized(),
+                *face_id,
+            )
+        })
+        .collect::<HashMap<_, _>>();
+
+    old.brush_faces[&old_brush]
+        .iter()
+        .filter_map(|old_face| {
+            let key = Plane3d::from(&old.face_planes[old_face]).quantized();
+            let new_face = new_faces.get(&key)?;
+
+            let old_texture = &old.textures[&old.face_textures[old_face]];
+            let new_texture = &new.textures[&new.face_textures[new_face]];
+
+            let unchanged = old_texture == new_texture
+                && old.face_offsets[old_face] == new.face_offsets[new_face]
+                && old.face_angles[old_face] == new.face_angles[new_face]
+                && old.face_scales[old_face] == new.face_scales[new_face]
+                && old.face_extensions[old_face] == new.face_extensions[new_face];
+
+            if unchanged {
+                None
+            } else {
+                Some(FaceTextureChange {
+                    old: *old_face,
+                    new: *new_face,
+                    old_texture: old_texture.clone(),
+                    new_texture: new_texture.clone(),
+                })
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::{box_brush, cube, entity};
+    use shalrath::repr::{Brush, Map};
+
+    fn light(targetname: &str, brightness: &str) -> shalrath::repr::Entity {
+        entity(
+            &[
+                ("classname", "light"),
+                ("targetname", targetname),
+                ("light", brightness),
+            ],
+            vec![],
+        )
+    }
+
+    fn diff(old: Vec<shalrath::repr::Entity>, new: Vec<shalrath::repr::Entity>) -> MapDiff {
+        map_diff(&GeoMap::new(Map(old)), &GeoMap::new(Map(new)))
+    }
+
+    fn world(brushes: Vec<Brush>) -> shalrath::repr::Entity {
+        entity(&[("classname", "worldspawn")], brushes)
+    }
+
+    #[test]
+    fn test_unchanged() {
+        let map = || vec![world(vec![cube(0.0), cube(64.0)]), light("a", "300")];
+        assert!(diff(map(), map()).is_empty());
+
+        // Reordering brushes matches them by geometry
+        let diff = diff(
+            vec![world(vec![cube(0.0), cube(64.0)])],
+            vec![world(vec![cube(64.0), cube(0.0)])],
+        );
+        assert!(diff.is_empty());
+        assert_eq!(
+            diff.brush_matches,
+            vec![(BrushId(0), BrushId(1)), (BrushId(1), BrushId(0))]
+                .into_iter()
+                .collect()
+        );
+    }
+
+    #[test]
+    fn test_brushes() {
+        let added = diff(
+            vec![world(vec![cube(0.0)])],
+            vec![world(vec![cube(0.0), cube(64.0)])],
+        );
+        assert_eq!(added.added_brushes, vec![BrushId(1)]);
+        assert!(added.removed_brushes.is_empty());
+        assert!(added.modified_brushes.is_empty());
+        assert!(added.modified_entities.is_empty());
+
+        let removed = diff(
+            vec![world(vec![cube(0.0), cube(64.0)])],
+            vec![world(vec![cube(64.0)])],
+        );
+        assert_eq!(removed.removed_brushes, vec![BrushId(0)]);
+        assert!(removed.added_brushes.is_empty());
+        assert!(removed.modified_brushes.is_empty());
+
+        // Dragging a single face keeps five of six planes
+        let modified = diff(
+            vec![world(vec![cube(0.0), cube(64.0)])],
+            vec![world(vec![
+                cube(0.0),
+                box_brush(
+                    nalgebra::vector![64.0, 0.0, 0.0],
+                    nalgebra::vector![128.0, 32.0, 32.0],
+                ),
+            ])],
+        );
+        assert_eq!(modified.modified_brushes, vec![(BrushId(1), BrushId(1))]);
+        assert!(modified.added_brushes.is_empty());
+        assert!(modified.removed_brushes.is_empty());
+    }
+
+    #[test]
+    fn test_retextured_faces() {
+        let mut retextured = cube(0.0);
+        retextured.0[4].texture = "sky".to_string();
+
+        let diff = diff(vec![world(vec![cube(0.0)])], vec![world(vec![retextured])]);
+        assert_eq!(
+            diff.retextured_faces,
+            vec![FaceTextureChange {
+                old: FaceId(4),
+                new: FaceId(4),
+                old_texture: crate::generate::TEXTURE.to_string(),
+                new_texture: "sky".to_string(),
+            }]
+        );
+        assert!(diff.modified_brushes.is_empty());
+    }
+
+    #[test]
+    fn test_entities() {
+        let added = diff(
+            vec![world(vec![cube(0.0)])],
+            vec![world(vec![cube(0.0)]), light("a", "300")],
+        );
+        assert_eq!(added.added_entities, vec![EntityId(1)]);
+        assert!(added.removed_entities.is_empty());
+
+        let removed = diff(
+            vec![world(vec![cube(0.0)]), light("a", "300"), light("b", "300")],
+            vec![world(vec![cube(0.0)]), light("b", "300")],
+        );
+        assert_eq!(removed.removed_entities, vec![EntityId(1)]);
+        assert_eq!(removed.entity_matches[&EntityId(2)], EntityId(1));
+        assert!(removed.added_entities.is_empty());
+        assert!(removed.modified_entities.is_empty());
+    }
+
+    #[test]
+    fn test_properties() {
+        let diff = diff(
+            vec![
+                entity(
+                    &[
+                        ("classname", "worldspawn"),
+                        ("message", "old"),
+                        ("wad", "base.wad"),
+                    ],
+                    vec![cube(0.0)],
+                ),
+                light("a", "300"),
+            ],
+            vec![
+                entity(
+                    &[
+                        ("classname", "worldspawn"),
+                        ("message", "new"),
+                        ("_sunlight", "200"),
+                    ],
+                    vec![cube(0.0)],
+                ),
+                light("a", "150"),
+            ],
+        );
+
+        assert!(diff.added_entities.is_empty());
+        assert!(diff.removed_entities.is_empty());
+
+        let change = |key: &str, old: Option<&str>, new: Option<&str>| PropertyChange {
+            key: key.to_string(),
+            old: old.map(str::to_string),
+            new: new.map(str::to_string),
+        };
+
+        assert_eq!(
+            diff.modified_entities,
+            vec![
+                EntityChange {
+                    old: EntityId(0),
+                    new: EntityId(0),
+                    properties: vec![
+                        change("_sunlight", None, Some("200")),
+                        change("message", Some("old"), Some("new")),
+                        change("wad", Some("base.wad"), None),
+                    ],
+                },
+                // Matched through its targetname
+                EntityChange {
+                    old: EntityId(1),
+                    new: EntityId(1),
+                    properties: vec![change("light", Some("300"), Some("150"))],
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::BTreeMap;
+
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use usage::Usage;
+
+use super::{entity_property_vector3, EntityCenters, EntityId, EntityPivots};
+use crate::{brush::BrushId, face::FaceVertices, BrushFaces, EntityProperties, Vector3};
+
+/// Method used to choose the local-space origin of a brush entity
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum EntityOriginMode {
+    /// Keep vertices in world space
+    World,
+    /// Use the average of the entity's brush centers
+    Center,
+    /// Use the minimum corner of the entity's axis-aligned bounding box
+    BoundsMin,
+    /// Use the entity's `origin` property, falling back to world space if absent
+    Property,
+    /// Use the entity's origin brush pivot, falling back to world space if absent
+    Pivot,
+}
+
+impl Default for EntityOriginMode {
+    fn default() -> Self {
+        EntityOriginMode::World
+    }
+}
+
+pub enum EntityOriginsTag {}
+
+/// Local-space origin for each brush entity
+pub type EntityOrigins = Usage<EntityOriginsTag, BTreeMap<EntityId, Vector3>>;
+
+/// Calculate entity origins, using `default_mode` for any entity not present in `entity_origin_modes`
+pub fn entity_origins(
+    entity_brushes: &BTreeMap<EntityId, Vec<BrushId>>,
+    entity_properties: &EntityProperties,
+    brush_faces: &BrushFaces,
+    face_vertices: &FaceVertices,
+    entity_centers: &EntityCenters,
+    entity_pivots: &EntityPivots,
+    entity_origin_modes: &BTreeMap<EntityId, EntityOriginMode>,
+    default_mode: EntityOriginMode,
+) -> EntityOrigins {
+    entity_brushes
+        .par_iter()
+        .map(|(entity_id, brush_ids)| {
+            let mode = entity_origin_modes
+                .get(entity_id)
+                .copied()
+                .unwrap_or(default_mode);
+
+            let origin = match mode {
+                EntityOriginMode::World => None,
+                EntityOriginMode::Center => Some(entity_centers[entity_id]),
+                EntityOriginMode::BoundsMin => brush_ids
+                    .iter()
+                    .flat_map(|brush_id| &brush_faces[brush_id])
+                    .flat_map(|face_id| &face_vertices[face_id])
+                    .copied()
+                    .reduce(|acc, vertex| acc.inf(&vertex)),
+                EntityOriginMode::Property => {
+                    entity_property_vector3(&entity_properties[entity_id], "origin")
+                }
+                EntityOriginMode::Pivot => entity_pivots.get(entity_id).copied(),
+            };
+
+            (*entity_id, origin.unwrap_or_else(Vector3::zeros))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        brush::{brush_centers, brush_entities},
+        entity::{entity_centers, entity_pivots},
+        face::{face_brushes, face_vertices_local},
+        generate::{box_brush, entity, pipeline, Pipeline},
+    };
+    use shalrath::repr::Map;
+
+    #[test]
+    fn test_entity_origins() {
+        let Pipeline {
+            geo_map,
+            vertices,
+            centers,
+            ..
+        } = pipeline(Map(vec![
+            entity(&[("classname", "worldspawn")], vec![]),
+            entity(
+                &[("classname", "func_door"), ("origin", "1 2 3")],
+                vec![box_brush(
+                    nalgebra::vector![16.0, 32.0, 0.0],
+                    nalgebra::vector![48.0, 64.0, 16.0],
+                )],
+            ),
+        ]));
+
+        let brush_centers = brush_centers(&geo_map.brush_faces, &centers);
+        let entity_centers = entity_centers(&geo_map.entity_brushes, &brush_centers);
+        let entity_pivots = entity_pivots(
+            &geo_map.entity_brushes,
+            &std::iter::empty::<BrushId>().collect(),
+            &brush_centers,
+        );
+
+        let door = EntityId(1);
+        let origin = |mode: EntityOriginMode| {
+            entity_origins(
+                &geo_map.entity_brushes,
+                &geo_map.entity_properties,
+                &geo_map.brush_faces,
+                &vertices,
+                &entity_centers,
+                &entity_pivots,
+                &BTreeMap::default(),
+                mode,
+            )[&door]
+        };
+
+        assert_eq!(EntityOriginMode::default(), EntityOriginMode::World);
+        assert_eq!(origin(EntityOriginMode::World), Vector3::zeros());
+        assert!(
+            (origin(EntityOriginMode::Center) - nalgebra::vector![32.0, 48.0, 8.0]).magnitude()
+                < 0.01
+        );
+        assert!(
+            (origin(EntityOriginMode::BoundsMin) - nalgebra::vector![16.0, 32.0, 0.0]).magnitude()
+                < 0.01
+        );
+        assert_eq!(
+            origin(EntityOriginMode::Property),
+            nalgebra::vector![1.0, 2.0, 3.0]
+        );
+
+        // No origin brush, so pivot falls back to world space
+        assert_eq!(origin(EntityOriginMode::Pivot), Vector3::zeros());
+
+        // Per-entity modes override the default
+        let modes = std::iter::once((door, EntityOriginMode::Property)).collect();
+        let origins = entity_origins(
+            &geo_map.entity_brushes,
+            &geo_map.entity_properties,
+            &geo_map.brush_faces,
+            &vertices,
+            &entity_centers,
+            &entity_pivots,
+            &modes,
+            EntityOriginMode::Center,
+        );
+        assert_eq!(origins[&door], nalgebra::vector![1.0, 2.0, 3.0]);
+
+        // Local vertices are offset by the origin
+        let face_brushes = face_brushes(&geo_map.brush_faces);
+        let brush_entities = brush_entities(&geo_map.entity_brushes);
+        let local = face_vertices_local(&vertices, &face_brushes, &brush_entities, &origins);
+        for (face_id, vertices) in vertices.iter() {
+            for (world, local) in vertices.iter().zip(&local[face_id]) {
+                assert_eq!(world - origins[&door], *local);
+            }
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use std::collections::BTreeMap;
+
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use usage::Usage;
+
+use super::EntityId;
+use crate::{
+    brush::{BrushCenters, BrushId, OriginBrushes},
+    Vector3,
+};
+
+pub enum EntityPivotsTag {}
+
+/// Rotation pivot for each entity containing an origin brush
+pub type EntityPivots = Usage<EntityPivotsTag, BTreeMap<EntityId, Vector3>>;
+
+// Calculate entity pivots from the centers of their origin brushes
+pub fn entity_pivots(
+    entity_brushes: &BTreeMap<EntityId, Vec<BrushId>>,
+    origin_brushes: &OriginBrushes,
+    brush_centers: &BrushCenters,
+) -> EntityPivots {
+    entity_brushes
+        .par_iter()
+        .flat_map(|(entity_id, brush_ids)| {
+            let origins = brush_ids
+                .iter()
+                .filter(|brush_id| origin_brushes.contains(brush_id))
+                .map(|brush_id| brush_centers[brush_id])
+                .collect::<Vec<_>>();
+
+            if origins.is_empty() {
+                return None;
+            }
+
+            let pivot = origins.iter().sum::<Vector3>() / origins.len() as f32;
+
+            Some((*entity_id, pivot))
+        })
+        .collect()
+}
@@ -0,0 +1,25 @@
+use shalrath::repr::{Properties, Property};
+
+use crate::Vector3;
+
+/// Fetch the value of an entity property by key
+pub fn entity_property<'a>(properties: &'a Properties, key: &str) -> Option<&'a str> {
+    properties
+        .0
+        .iter()
+        .find(|Property { key: k, .. }| k == key)
+        .map(|Property { value, .. }| value.as_str())
+}
+
+/// Parse a whitespace-separated `x y z` property such as `origin` or `_color`
+pub fn entity_property_vector3(properties: &Properties, key: &str) -> Option<Vector3> {
+    let mut components = entity_property(properties, key)?
+        .split_whitespace()
+        .map(|component| component.parse::<f32>().ok());
+
+    let x = components.next()??;
+    let y = components.next()??;
+    let z = components.next()??;
+
+    Some(nalgebra::vector![x, y, z])
+}
@@ -0,0 +1,153 @@
+use std::collections::BTreeMap;
+
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use shalrath::repr::Properties;
+use usage::{AsUsage, Usage};
+
+use super::{entity_property, EntityId};
+use crate::{stable_id::StableHasher, StableId};
+
+pub enum EntityStableIdsTag {}
+pub enum StableEntitiesTag {}
+
+pub type EntityStableIds = Usage<EntityStableIdsTag, BTreeMap<EntityId, StableId>>;
+
+/// Lookup from [`StableId`] to the current [`EntityId`]
+pub type StableEntities = Usage<StableEntitiesTag, BTreeMap<StableId, EntityId>>;
+
+/// Identify entities by their most specific identifying property
+///
+/// In order of preference: TrenchBroom's `_tb_id` for layers and groups,
+/// `targetname` together with `classname`, and finally `classname` alone.
+/// Entities without a name are told apart by their order among entities of the same class,
+/// so editing their other properties or moving their brushes keeps the ID.
+pub fn entity_stable_ids(
+    entities: &[EntityId],
+    entity_properties: &BTreeMap<EntityId, Properties>,
+) -> EntityStableIds {
+    let hashes = entities
+        .par_iter()
+        .map(|entity_id| {
+            let properties = &entity_properties[entity_id];
+            let classname = entity_property(properties, "classname").unwrap_or_default();
+
+            let mut hasher = StableHasher::default();
+            if let Some(tb_id) = entity_property(properties, "_tb_id") {
+                hasher.write_str("_tb_id");
+                hasher.write_str(entity_property(properties, "_tb_type").unwrap_or_default());
+                hasher.write_str(tb_id);
+            } else if let Some(targetname) = entity_property(properties, "targetname") {
+                hasher.write_str("targetname");
+                hasher.write_str(classname);
+                hasher.write_str(targetname);
+            } else {
+                hasher.write_str("classname");
+                hasher.write_str(classname);
+            }
+
+            (*entity_id, hasher.finish())
+        })
+        .collect::<Vec<_>>();
+
+    EntityStableIdsTag::as_usage(crate::stable_id::stable_ids(hashes))
+}
+
+pub fn stable_entities(entity_stable_ids: &EntityStableIds) -> StableEntities {
+    entity_stable_ids
+        .iter()
+        .map(|(entity_id, stable_id)| (*stable_id, *entity_id))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        generate::{cube, entity},
+        GeoMap,
+    };
+    use shalrath::repr::Map;
+
+    fn stable_ids(map: Map) -> EntityStableIds {
+        let geo_map = GeoMap::new(map);
+        entity_stable_ids(&geo_map.entities, &geo_map.entity_properties)
+    }
+
+    #[test]
+    fn test_entity_stable_ids() {
+        let old = stable_ids(Map(vec![
+            entity(&[("classname", "worldspawn")], vec![cube(0.0)]),
+            entity(
+                &[("classname", "func_door"), ("targetname", "door")],
+                vec![cube(64.0)],
+            ),
+            entity(
+                &[("classname", "func_detail"), ("_phong", "1")],
+                vec![cube(128.0)],
+            ),
+            entity(
+                &[
+                    ("classname", "light"),
+                    ("origin", "0 0 64"),
+                    ("light", "300"),
+                ],
+                vec![],
+            ),
+            entity(&[("classname", "light"), ("origin", "0 0 128")], vec![]),
+        ]));
+
+        // Unnamed entities of the same class are told apart by occurrence
+        assert_eq!(old[&EntityId(3)].hash, old[&EntityId(4)].hash);
+        assert_eq!(old[&EntityId(3)].occurrence, 0);
+        assert_eq!(old[&EntityId(4)].occurrence, 1);
+
+        // Edit properties and move brushes
+        let new = stable_ids(Map(vec![
+            entity(
+                &[("classname", "worldspawn"), ("message", "edited")],
+                vec![cube(16.0)],
+            ),
+            entity(
+                &[
+                    ("classname", "func_door"),
+                    ("targetname", "door"),
+                    ("speed", "200"),
+                ],
+                vec![cube(80.0)],
+            ),
+            entity(
+                &[("classname", "func_detail"), ("_phong", "0")],
+                vec![cube(160.0)],
+            ),
+            entity(
+                &[
+                    ("classname", "light"),
+                    ("origin", "32 0 64"),
+                    ("light", "150"),
+                ],
+                vec![],
+            ),
+            entity(
+                &[
+                    ("classname", "light"),
+                    ("origin", "0 0 128"),
+                    ("_color", "1 0 0"),
+                ],
+                vec![],
+            ),
+        ]));
+
+        assert_eq!(*old, *new);
+
+        // Renaming an entity changes its ID
+        let renamed = stable_ids(Map(vec![
+            entity(&[("classname", "worldspawn")], vec![cube(0.0)]),
+            entity(
+                &[("classname", "func_door"), ("targetname", "gate")],
+                vec![cube(64.0)],
+            ),
+        ]));
+        assert_eq!(renamed[&EntityId(0)], old[&EntityId(0)]);
+        assert_ne!(renamed[&EntityId(1)], old[&EntityId(1)]);
+    }
+}
@@ -1,5 +1,13 @@
 mod entity_centers;
 mod entity_id;
+mod entity_origins;
+mod entity_pivots;
+mod entity_property;
+mod entity_stable_ids;
 
 pub use entity_centers::*;
 pub use entity_id::*;
+pub use entity_origins::*;
+pub use entity_pivots::*;
+pub use entity_property::*;
+pub use entity_stable_ids::*;
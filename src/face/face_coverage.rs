@@ -0,0 +1,410 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use usage::Usage;
+
+use super::{opposing_faces, plane_buckets, FaceId, FaceIndices, FacePlanes, FaceVertices};
+use crate::{Plane3d, Vector2, Vector3, EPSILON};
+
+/// The parts of a face hidden by, and left visible around, opposing coincident faces
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FaceCoverage {
+    /// Opposing faces that overlap this face
+    pub covering: Vec<FaceId>,
+    /// Convex regions of the face hidden by opposing faces
+    pub covered: Vec<Vec<Vector3>>,
+    /// Convex regions of the face left visible, empty if the face is fully covered
+    pub visible: Vec<Vec<Vector3>>,
+}
+
+impl FaceCoverage {
+    pub fn is_fully_covered(&self) -> bool {
+        self.visible.is_empty()
+    }
+}
+
+pub enum FaceCoveragesTag {}
+pub enum CoveredFacesTag {}
+
+/// Coverage of each face that overlaps at least one opposing coincident face
+pub type FaceCoverages = Usage<FaceCoveragesTag, BTreeMap<FaceId, FaceCoverage>>;
+
+/// Faces entirely hidden by opposing coincident faces
+pub type CoveredFaces = Usage<CoveredFacesTag, BTreeSet<FaceId>>;
+
+/// Classify the overlap between opposing coplanar faces
+///
+/// Unlike [`face_duplicates`](super::face_duplicates), faces need not share vertices,
+/// so a face touching a larger or offset face is split into covered and visible convex regions.
+/// Faces are bucketed by plane, so each face is only compared against
+/// faces whose plane opposes its own.
+/// Overlaps are computed in 2D using the unit in-plane [`axes`](Plane3d::axes) of each face.
+pub fn face_coverage(
+    faces: &Vec<FaceId>,
+    face_planes: &FacePlanes,
+    face_vertices: &FaceVertices,
+    face_indices: &FaceIndices,
+) -> FaceCoverages {
+    let buckets = plane_buckets(faces, face_planes);
+
+    faces
+        .par_iter()
+        .flat_map(|lhs_id| {
+            let lhs_plane = &face_planes[lhs_id];
+            let lhs_axes = lhs_plane.axes();
+
+            let lhs_polygon = polygon_2d(&lhs_axes, &face_vertices[lhs_id], &face_indices[lhs_id])?;
+
+            let mut candidates = opposing_faces(&buckets, face_planes, lhs_id).collect::<Vec<_>>();
+            candidates.sort_unstable();
+
+            let mut coverage = FaceCoverage::default();
+            let mut visible = vec![lhs_polygon];
+
+            for rhs_id in candidates {
+                let rhs_polygon =
+                    match polygon_2d(&lhs_axes, &face_vertices[rhs_id], &face_indices[rhs_id]) {
+                        Some(polygon) => polygon,
+                        None => continue,
+                    };
+
+                // Split each remaining visible region into the parts inside and outside RHS
+                let mut covered = vec![];
+                let mut remaining = vec![];
+                for polygon in visible {
+                    let (inside, outside) = split_convex(polygon, &rhs_polygon);
+                    covered.extend(inside);
+                    remaining.extend(outside);
+                }
+                visible = remaining;
+
+                if !covered.is_empty() {
+                    coverage.covering.push(*rhs_id);
+                    coverage.covered.extend(covered);
+                }
+            }
+
+            if coverage.covering.is_empty() {
+                return None;
+            }
+
+            let to_world = |polygon: Vec<Vector2>| polygon_3d(&lhs_axes, lhs_plane, &polygon);
+
+            coverage.covered = coverage.covered.into_iter().filter_map(to_world).collect();
+            coverage.visible = visible.into_iter().filter_map(to_world).collect();
+
+            if coverage.covered.is_empty() {
+                return None;
+            }
+
+            Some((*lhs_id, coverage))
+        })
+        .collect()
+}
+
+/// Faces whose every region is hidden by opposing coincident faces
+pub fn covered_faces(face_coverages: &FaceCoverages) -> CoveredFaces {
+    face_coverages
+        .iter()
+        .filter(|(_, coverage)| coverage.is_fully_covered())
+        .map(|(face_id, _)| *face_id)
+        .collect()
+}
+
+/// Project a face's ordered vertices onto a pair of in-plane axes, wound counter-clockwise
+fn polygon_2d(
+    (u, v): &(Vector3, Vector3),
+    vertices: &[Vector3],
+    indices: &[usize],
+) -> Option<Vec<Vector2>> {
+    if indices.len() < 3 {
+        return None;
+    }
+
+    let mut polygon = indices
+        .iter()
+        .map(|i| nalgebra::vector![vertices[*i].dot(u), vertices[*i].dot(v)])
+        .collect::<Vec<_>>();
+
+    if signed_area(&polygon) < 0.0 {
+        polygon.reverse();
+    }
+
+    Some(polygon)
+}
+
+/// Lift a 2D polygon back onto the face plane, or `None` if it has no area
+///
+/// The axes are orthonormal and perpendicular to the plane normal, so no inverse is needed.
+fn polygon_3d(
+    (u, v): &(Vector3, Vector3),
+    plane: &Plane3d,
+    polygon: &[Vector2],
+) -> Option<Vec<Vector3>> {
+    let origin = plane.normal() * plane.distance();
+
+    let polygon = polygon
+        .iter()
+        .map(|p| origin + u * p.x + v * p.y)
+        .collect::<Vec<_>>();
+
+    let mut area = Vector3::zeros();
+    for i in 0..polygon.len() {
+        area += polygon[i].cross(&polygon[(i + 1) % polygon.len()]);
+    }
+
+    if area.magnitude() * 0.5 <= EPSILON {
+        return None;
+    }
+
+    Some(polygon)
+}
+
+fn signed_area(polygon: &[Vector2]) -> f32 {
+    let mut area = 0.0;
+    for i in 0..polygon.len() {
+        let a = polygon[i];
+        let b = polygon[(i + 1) % polygon.len()];
+        area += a.perp(&b);
+    }
+    area * 0.5
+}
+
+/// Split convex `polygon` into the region inside convex `clip`, and convex regions outside it
+///
+/// Both polygons are expected to be wound counter-clockwise.
+fn split_convex(
+    polygon: Vec<Vector2>,
+    clip: &[Vector2],
+) -> (Option<Vec<Vector2>>, Vec<Vec<Vector2>>) {
+    let mut inside = polygon.clone();
+    let mut outside = vec![];
+
+    for i in 0..clip.len() {
+        let a = clip[i];
+        let b = clip[(i + 1) % clip.len()];
+
+        // Peel off the part of the remaining polygon beyond this edge
+        let beyond = clip_half_plane(&inside, &b, &a);
+        inside = clip_half_plane(&inside, &a, &b);
+
+        if signed_area(&inside) <= EPSILON {
+            // No overlap, so leave the polygon whole
+            return (None, vec![polygon]);
+        }
+
+        if signed_area(&beyond) > EPSILON {
+            outside.push(beyond);
+        }
+    }
+
+    (Some(inside), outside)
+}
+
+/// Clip a convex polygon to the half-plane left of the directed line from `a` to `b`
+fn clip_half_plane(polygon: &[Vector2], a: &Vector2, b: &Vector2) -> Vec<Vector2> {
+    let edge = b - a;
+    let length = edge.magnitude();
+    if length <= EPSILON {
+        return polygon.to_vec();
+    }
+
+    let distance = |p: &Vector2| edge.perp(&(p - a)) / length;
+
+    let mut clipped = vec![];
+    for i in 0..polygon.len() {
+        let p0 = polygon[i];
+        let p1 = polygon[(i + 1) % polygon.len()];
+        let d0 = distance(&p0);
+        let d1 = distance(&p1);
+
+        if d0 >= -EPSILON {
+            clipped.push(p0);
+        }
+
+        // Add the crossing point when the edge passes strictly through the line
+        if (d0 < -EPSILON && d1 > EPSILON) || (d0 > EPSILON && d1 < -EPSILON) {
+            clipped.push(p0 + (p1 - p0) * (d0 / (d0 - d1)));
+        }
+    }
+
+    clipped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::{box_brush, pipeline, worldspawn};
+
+    fn coverages(brushes: Vec<shalrath::repr::Brush>) -> FaceCoverages {
+        let pipeline = pipeline(worldspawn(brushes));
+        face_coverage(
+            &pipeline.geo_map.faces,
+            &pipeline.planes,
+            &pipeline.vertices,
+            &pipeline.indices,
+        )
+    }
+
+    /// Returns true if `point` lies inside a convex polygon in the XY plane, of either winding
+    fn contains_xy(polygon: &[Vector3], point: Vector2) -> bool {
+        let sides = (0..polygon.len())
+            .map(|i| {
+                let a = polygon[i].xy();
+                let b = polygon[(i + 1) % polygon.len()].xy();
+                (b - a).perp(&(point - a))
+            })
+            .collect::<Vec<_>>();
+
+        sides.iter().all(|side| *side > 0.0) || sides.iter().all(|side| *side < 0.0)
+    }
+
+    fn total_area(polygons: &[Vec<Vector3>]) -> f32 {
+        let mut total = 0.0;
+        for polygon in polygons {
+            let mut area = Vector3::zeros();
+            for i in 0..polygon.len() {
+                area += polygon[i].cross(&polygon[(i + 1) % polygon.len()]);
+            }
+            total += area.magnitude() * 0.5;
+        }
+        total
+    }
+
+    #[test]
+    fn test_partially_coincident_faces() {
+        // A small box resting on the middle of a larger one
+        let coverages = coverages(vec![
+            box_brush(
+                nalgebra::vector![0.0, 0.0, 0.0],
+                nalgebra::vector![64.0, 64.0, 16.0],
+            ),
+            box_brush(
+                nalgebra::vector![16.0, 16.0, 16.0],
+                nalgebra::vector![48.0, 48.0, 32.0],
+            ),
+        ]);
+
+        let large_top = FaceId(4);
+        let small_bottom = FaceId(11);
+        assert_eq!(coverages.len(), 2);
+
+        let small = &coverages[&small_bottom];
+        assert_eq!(small.covering, vec![large_top]);
+        assert!(small.is_fully_covered());
+        assert!((total_area(&small.covered) - 1024.0).abs() < 0.1);
+
+        let large = &coverages[&large_top];
+        assert_eq!(large.covering, vec![small_bottom]);
+        assert!(!large.is_fully_covered());
+        assert!((total_area(&large.covered) - 1024.0).abs() < 0.1);
+        assert!((total_area(&large.visible) - 3072.0).abs() < 0.1);
+
+        for polygon in large.visible.iter().chain(&large.covered) {
+            for vertex in polygon {
+                assert!((vertex.z - 16.0).abs() < EPSILON);
+            }
+        }
+
+        assert_eq!(
+            *covered_faces(&coverages),
+            std::iter::once(small_bottom).collect::<BTreeSet<_>>()
+        );
+    }
+
+    #[test]
+    fn test_covered_by_two_faces() {
+        // Two boxes side by side, together covering the top of a third
+        let coverages = coverages(vec![
+            box_brush(
+                nalgebra::vector![0.0, 0.0, 0.0],
+                nalgebra::vector![64.0, 32.0, 16.0],
+            ),
+            box_brush(
+                nalgebra::vector![0.0, 0.0, 16.0],
+                nalgebra::vector![32.0, 32.0, 32.0],
+            ),
+            box_brush(
+                nalgebra::vector![32.0, 0.0, 16.0],
+                nalgebra::vector![64.0, 32.0, 32.0],
+            ),
+        ]);
+
+        let large_top = FaceId(4);
+        let left_bottom = FaceId(11);
+        let right_bottom = FaceId(17);
+
+        let large = &coverages[&large_top];
+        assert_eq!(large.covering, vec![left_bottom, right_bottom]);
+        assert!(large.is_fully_covered());
+        assert!((total_area(&large.covered) - 2048.0).abs() < 0.1);
+
+        for small in [left_bottom, right_bottom] {
+            assert_eq!(coverages[&small].covering, vec![large_top]);
+            assert!(coverages[&small].is_fully_covered());
+        }
+
+        // The walls between the two upper boxes cover one another too
+        let left_wall = FaceId(6);
+        let right_wall = FaceId(13);
+        assert_eq!(coverages[&left_wall].covering, vec![right_wall]);
+        assert_eq!(coverages[&right_wall].covering, vec![left_wall]);
+        assert_eq!(coverages.len(), 5);
+
+        assert_eq!(
+            *covered_faces(&coverages),
+            vec![large_top, left_wall, left_bottom, right_wall, right_bottom]
+                .into_iter()
+                .collect::<BTreeSet<_>>()
+        );
+    }
+
+    #[test]
+    fn test_l_shaped_remainder() {
+        // A box resting on one corner of a larger one, leaving an L-shaped region visible
+        let coverages = coverages(vec![
+            box_brush(
+                nalgebra::vector![0.0, 0.0, 0.0],
+                nalgebra::vector![64.0, 64.0, 16.0],
+            ),
+            box_brush(
+                nalgebra::vector![0.0, 0.0, 16.0],
+                nalgebra::vector![32.0, 32.0, 32.0],
+            ),
+        ]);
+        assert_eq!(coverages.len(), 2);
+
+        let large = &coverages[&FaceId(4)];
+        assert_eq!(large.covering, vec![FaceId(11)]);
+        assert!(!large.is_fully_covered());
+        assert!((total_area(&large.covered) - 1024.0).abs() < 0.1);
+        assert!((total_area(&large.visible) - 3072.0).abs() < 0.1);
+
+        let visible = |point: Vector2| {
+            large
+                .visible
+                .iter()
+                .any(|polygon| contains_xy(polygon, point))
+        };
+        let covered = |point: Vector2| {
+            large
+                .covered
+                .iter()
+                .any(|polygon| contains_xy(polygon, point))
+        };
+
+        for point in [
+            nalgebra::vector![48.0, 16.0],
+            nalgebra::vector![16.0, 48.0],
+            nalgebra::vector![48.0, 48.0],
+        ] {
+            assert!(visible(point));
+            assert!(!covered(point));
+        }
+
+        let corner = nalgebra::vector![16.0, 16.0];
+        assert!(!visible(corner));
+        assert!(covered(corner));
+    }
+}
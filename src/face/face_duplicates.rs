@@ -1,73 +1,267 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, HashMap};
 
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use usage::Usage;
 
 use super::{FaceId, FaceVertices};
-use crate::{FacePlanes, EPSILON};
+use crate::{FacePlanes, Plane3d, Vector3, EPSILON};
 
 pub enum FaceDuplicatesTag {}
 
 /// The set of opposing faces that share the same set of vertices
 pub type FaceDuplicates = Usage<FaceDuplicatesTag, BTreeSet<(FaceId, FaceId)>>;
 
+/// Size of the grid plane normal components are snapped to for bucketing
+const NORMAL_CELL: f32 = 0.25;
+
+/// Largest normal difference accepted by [`Plane3d::opposes`], `sqrt(2 * EPSILON)`
+const NORMAL_TOLERANCE: f32 = 0.045;
+
+/// Size of the grid plane distances are snapped to for bucketing
+const DISTANCE_CELL: f32 = 1.0 / 16.0;
+
+type PlaneCell = [i64; 4];
+
+/// Find opposing faces that share the same set of vertices
+///
+/// Faces are bucketed by plane, so each face is only compared against
+/// faces whose plane opposes its own, and vertices are matched to within [`EPSILON`].
+/// Produces the same result as comparing every pair of faces.
 pub fn face_duplicates(
     planes: &Vec<FaceId>,
     face_planes: &FacePlanes,
     face_vertices: &FaceVertices,
 ) -> FaceDuplicates {
+    let buckets = plane_buckets(planes, face_planes);
+
     planes
         .par_iter()
         .flat_map(|lhs_id| {
-            let lhs_verts = &face_vertices[&lhs_id];
-            let lhs_plane = &face_planes[&lhs_id];
+            let lhs_verts = &face_vertices[lhs_id];
+
+            opposing_faces(&buckets, face_planes, lhs_id)
+                .filter(|rhs_id| vertices_match(lhs_verts, &face_vertices[*rhs_id]))
+                .flat_map(|rhs_id| [(*lhs_id, *rhs_id), (*rhs_id, *lhs_id)])
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Returns true if two faces oppose one another and share the same set of vertices
+pub(crate) fn faces_duplicate(
+    lhs_plane: &Plane3d,
+    lhs_verts: &[Vector3],
+    rhs_plane: &Plane3d,
+    rhs_verts: &[Vector3],
+) -> bool {
+    // Skip faces that don't lie on the same plane
+    if !lhs_plane.opposes(rhs_plane) {
+        return false;
+    }
+
+    vertices_match(lhs_verts, rhs_verts)
+}
+
+/// Returns true if every vertex of each face lies within [`EPSILON`] of a vertex of the other
+fn vertices_match(lhs_verts: &[Vector3], rhs_verts: &[Vector3]) -> bool {
+    // Skip comparing with faces of different vertex count
+    if lhs_verts.len() != rhs_verts.len() {
+        return false;
+    }
+
+    let coincide = |lhs: &[Vector3], rhs: &[Vector3]| {
+        lhs.iter()
+            .all(|l| rhs.iter().any(|r| (l - r).magnitude() < EPSILON))
+    };
+
+    coincide(lhs_verts, rhs_verts) && coincide(rhs_verts, lhs_verts)
+}
+
+/// Faces bucketed by the grid cell nearest to their plane
+pub(crate) type PlaneBuckets = HashMap<PlaneCell, Vec<FaceId>>;
+
+pub(crate) fn plane_buckets(faces: &[FaceId], face_planes: &FacePlanes) -> PlaneBuckets {
+    let mut buckets = PlaneBuckets::default();
+    for face_id in faces {
+        buckets
+            .entry(plane_cell(&face_planes[face_id]))
+            .or_default()
+            .push(*face_id);
+    }
+    buckets
+}
+
+/// Bucketed faces other than `face_id` whose plane [`opposes`](Plane3d::opposes) its own
+pub(crate) fn opposing_faces<'a>(
+    buckets: &'a PlaneBuckets,
+    face_planes: &'a FacePlanes,
+    face_id: &'a FaceId,
+) -> impl Iterator<Item = &'a FaceId> {
+    let plane = &face_planes[face_id];
+
+    let opposite = Plane3d {
+        n: -plane.n,
+        d: -plane.d,
+    };
+
+    // Planes near a cell boundary may have been bucketed in the neighbouring cell
+    plane_cells(&opposite)
+        .into_iter()
+        .filter_map(move |cell| buckets.get(&cell))
+        .flatten()
+        .filter(move |rhs_id| *rhs_id != face_id)
+        .filter(move |rhs_id| plane.opposes(&face_planes[*rhs_id]))
+}
+
+/// Grid cell nearest to a plane
+fn plane_cell(plane: &Plane3d) -> PlaneCell {
+    let snap = |value: f32, cell: f32| (value / cell).round() as i64;
+    [
+        snap(plane.n.x, NORMAL_CELL),
+        snap(plane.n.y, NORMAL_CELL),
+        snap(plane.n.z, NORMAL_CELL),
+        snap(plane.d, DISTANCE_CELL),
+    ]
+}
 
+/// Every grid cell within tolerance of a plane, starting with the nearest
+///
+/// Both tolerances are under half a cell, so a matching plane is at most one cell away on each axis.
+fn plane_cells(plane: &Plane3d) -> Vec<PlaneCell> {
+    let axis_cells = |value: f32, cell: f32, tolerance: f32| {
+        let scaled = value / cell;
+        let nearest = scaled.round();
+        let offset = scaled - nearest;
+
+        let mut cells = vec![nearest as i64];
+        if offset.abs() >= 0.5 - tolerance / cell {
+            cells.push((nearest + offset.signum()) as i64);
+        }
+        cells
+    };
+
+    let xs = axis_cells(plane.n.x, NORMAL_CELL, NORMAL_TOLERANCE);
+    let ys = axis_cells(plane.n.y, NORMAL_CELL, NORMAL_TOLERANCE);
+    let zs = axis_cells(plane.n.z, NORMAL_CELL, NORMAL_TOLERANCE);
+    let ds = axis_cells(plane.d, DISTANCE_CELL, EPSILON);
+
+    let mut cells = vec![];
+    for x in &xs {
+        for y in &ys {
+            for z in &zs {
+                for d in &ds {
+                    cells.push([*x, *y, *z, *d]);
+                }
+            }
+        }
+    }
+    cells
+}
+
+/// Reference implementation of [`face_duplicates`] comparing every pair of faces
+#[cfg(test)]
+fn face_duplicates_exhaustive(
+    planes: &Vec<FaceId>,
+    face_planes: &FacePlanes,
+    face_vertices: &FaceVertices,
+) -> FaceDuplicates {
+    planes
+        .par_iter()
+        .flat_map(|lhs_id| {
             planes
                 .par_iter()
-                .flat_map(move |rhs_id| {
-                    let rhs_verts = &face_vertices[&rhs_id];
-                    let rhs_plane = &face_planes[&rhs_id];
-
-                    // Skip comparing with self
-                    if lhs_id == rhs_id {
-                        return None;
-                    }
-
-                    // Skip faces that don't lie on the same plane
-                    if !lhs_plane.opposes(rhs_plane) {
-                        return None;
-                    }
-
-                    // Skip comparing with faces of different vertex count
-                    if lhs_verts.len() != rhs_verts.len() {
-                        return None;
-                    }
-
-                    let vert_count = lhs_verts.len();
-
-                    // Compare vertices
-                    let identical_count: usize = lhs_verts
-                        .par_iter()
-                        .flat_map(|lhs_vert| {
-                            rhs_verts.par_iter().map(move |rhs_vert| {
-                                let delta = (lhs_vert - rhs_vert).magnitude();
-                                if delta < EPSILON {
-                                    1
-                                } else {
-                                    0
-                                }
-                            })
-                        })
-                        .sum();
-
-                    if identical_count != vert_count {
-                        return None;
-                    }
-
-                    // Add faces to set
-                    Some([(*lhs_id, *rhs_id), (*rhs_id, *lhs_id)])
+                .filter(move |rhs_id| {
+                    *rhs_id != lhs_id
+                        && faces_duplicate(
+                            &face_planes[lhs_id],
+                            &face_vertices[lhs_id],
+                            &face_planes[*rhs_id],
+                            &face_vertices[*rhs_id],
+                        )
                 })
-                .flatten()
+                .map(move |rhs_id| (*lhs_id, *rhs_id))
         })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::{self, box_brush, box_grid, pipeline, worldspawn, Pipeline};
+
+    /// Run both implementations, asserting that they agree
+    fn duplicates(map: shalrath::repr::Map) -> FaceDuplicates {
+        let Pipeline {
+            geo_map,
+            planes,
+            vertices,
+            ..
+        } = pipeline(map);
+
+        let face_duplicates = face_duplicates(&geo_map.faces, &planes, &vertices);
+        let expected = face_duplicates_exhaustive(&geo_map.faces, &planes, &vertices);
+        assert_eq!(*face_duplicates, *expected);
+
+        face_duplicates
+    }
+
+    #[test]
+    fn test_touching_boxes() {
+        // Seven shared walls, each reported in both directions
+        let face_duplicates = duplicates(box_grid([3, 2, 1], 64.0, 0.0));
+        assert_eq!(face_duplicates.len(), 14);
+
+        for (lhs, rhs) in face_duplicates.iter() {
+            assert!(face_duplicates.contains(&(*rhs, *lhs)));
+        }
+
+        assert!(duplicates(box_grid([3, 2, 1], 64.0, 8.0)).is_empty());
+    }
+
+    #[test]
+    fn test_offset_boxes() {
+        // Touching along a plane, but the shared faces differ in extent
+        let face_duplicates = duplicates(worldspawn(vec![
+            box_brush(
+                nalgebra::vector![0.0, 0.0, 0.0],
+                nalgebra::vector![64.0, 64.0, 64.0],
+            ),
+            box_brush(
+                nalgebra::vector![64.0, 16.0, 0.0],
+                nalgebra::vector![128.0, 80.0, 64.0],
+            ),
+        ]));
+        assert!(face_duplicates.is_empty());
+    }
+
+    #[test]
+    fn test_near_cell_boundary() {
+        // Coincident to within EPSILON, but either side of a rounding boundary
+        let face_duplicates = duplicates(worldspawn(vec![
+            box_brush(
+                nalgebra::vector![0.0, 0.0, 0.0],
+                nalgebra::vector![64.0004, 64.0, 64.0],
+            ),
+            box_brush(
+                nalgebra::vector![64.0006, 0.0, 0.0],
+                nalgebra::vector![128.0, 64.0, 64.0],
+            ),
+        ]));
+
+        assert_eq!(
+            *face_duplicates,
+            vec![(FaceId(0), FaceId(7)), (FaceId(7), FaceId(0))]
+                .into_iter()
+                .collect::<BTreeSet<_>>()
+        );
+    }
+
+    #[test]
+    fn test_matches_exhaustive() {
+        assert!(!duplicates(box_grid([3, 3, 2], 64.0, 0.0)).is_empty());
+        duplicates(generate::staircase(16));
+        duplicates(generate::cylinders(4, 12));
+        duplicates(generate::overlapping_boxes(24, 7));
+        duplicates(generate::large_world(2));
+    }
+}
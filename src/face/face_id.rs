@@ -1,6 +1,7 @@
 use std::fmt::Display;
 
 #[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FaceId(pub usize);
 
 impl Display for FaceId {
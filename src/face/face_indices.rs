@@ -4,9 +4,15 @@ use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use usage::Usage;
 
 use super::{FaceCenters, FaceId, FaceVertices};
-use crate::{vector3_from_point, FacePlanes, FaceTrianglePlanes};
+use crate::{FacePlanes, Plane3d, Vector3, EPSILON};
 
+/// Vertex winding order
+///
+/// `Clockwise` produces polygons whose right-handed normal matches the face plane normal,
+/// i.e. vertices appear clockwise when viewed from behind the face looking along its normal.
+/// `CounterClockwise` produces the reverse.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum FaceWinding {
     Clockwise,
     CounterClockwise,
@@ -18,7 +24,6 @@ pub type FaceIndices = Usage<FaceIndicesTag, BTreeMap<FaceId, Vec<usize>>>;
 
 // Generate face indices with the specified winding
 pub fn face_indices(
-    face_planes: &FaceTrianglePlanes,
     geo_planes: &FacePlanes,
     face_vertices: &FaceVertices,
     face_centers: &FaceCenters,
@@ -27,37 +32,193 @@ pub fn face_indices(
     face_vertices
         .par_iter()
         .map(|(plane_id, vertices)| {
-            let face_plane = &face_planes[&plane_id];
-            let plane = &geo_planes[&plane_id];
-            let plane_center = &face_centers[&plane_id];
-
-            let plane_v0 = vector3_from_point(face_plane.v0);
-            let plane_v1 = vector3_from_point(face_plane.v1);
-            let u_axis = (plane_v1 - plane_v0).normalize();
-            let v_axis = plane.normal().cross(&u_axis);
-
-            let mut indices = (0..vertices.len()).collect::<Vec<_>>();
-            indices.sort_unstable_by(|lhs, rhs| {
-                let lhs_v = &vertices[*lhs] - plane_center;
-                let rhs_v = &vertices[*rhs] - plane_center;
-
-                let lhs_pu = lhs_v.dot(&u_axis);
-                let lhs_pv = lhs_v.dot(&v_axis);
-
-                let rhs_pu = rhs_v.dot(&u_axis);
-                let rhs_pv = rhs_v.dot(&v_axis);
-
-                let lhs_angle = lhs_pv.atan2(lhs_pu);
-                let rhs_angle = rhs_pv.atan2(rhs_pu);
-
-                if winding == FaceWinding::CounterClockwise {
-                    rhs_angle.partial_cmp(&lhs_angle)
-                } else {
-                    lhs_angle.partial_cmp(&rhs_angle)
-                }
-                .unwrap_or(Ordering::Equal)
-            });
-            (*plane_id, indices)
+            let plane = &geo_planes[plane_id];
+            let plane_center = &face_centers[plane_id];
+
+            (
+                *plane_id,
+                polygon_indices(vertices, plane.normal(), plane_center, winding),
+            )
         })
         .collect()
 }
+
+/// Order the vertices of a convex planar polygon
+///
+/// Duplicate and collinear vertices are omitted, and the result always starts
+/// from the lexicographically smallest remaining vertex,
+/// so output is independent of input order and of how the plane was defined.
+pub fn polygon_indices(
+    vertices: &[Vector3],
+    normal: &Vector3,
+    center: &Vector3,
+    winding: FaceWinding,
+) -> Vec<usize> {
+    // Canonical in-plane basis derived from the normal alone
+    let (u_axis, v_axis) = Plane3d { n: *normal, d: 0.0 }.axes();
+
+    // Drop near-duplicate vertices, keeping the first occurrence
+    let mut indices = (0..vertices.len())
+        .filter(|i| !(0..*i).any(|j| (vertices[*i] - vertices[j]).magnitude() < EPSILON))
+        .collect::<Vec<_>>();
+
+    let angle = |i: usize| {
+        let v = vertices[i] - center;
+        v.dot(&v_axis).atan2(v.dot(&u_axis))
+    };
+
+    // Sort counter-clockwise about the normal, breaking ties deterministically
+    indices.sort_unstable_by(|lhs, rhs| {
+        angle(*lhs)
+            .total_cmp(&angle(*rhs))
+            .then_with(|| {
+                let lhs_distance = (vertices[*lhs] - center).magnitude();
+                let rhs_distance = (vertices[*rhs] - center).magnitude();
+                lhs_distance.total_cmp(&rhs_distance)
+            })
+            .then_with(|| lhs.cmp(rhs))
+    });
+
+    // Remove collinear vertices until none remain
+    loop {
+        let n = indices.len();
+        if n < 3 {
+            break;
+        }
+
+        let collinear = (0..n).find(|i| {
+            let prev = &vertices[indices[(i + n - 1) % n]];
+            let curr = &vertices[indices[*i]];
+            let next = &vertices[indices[(i + 1) % n]];
+
+            let a = curr - prev;
+            let b = next - curr;
+            a.cross(&b).magnitude() <= EPSILON * a.magnitude() * b.magnitude()
+        });
+
+        match collinear {
+            Some(i) => {
+                indices.remove(i);
+            }
+            None => break,
+        }
+    }
+
+    if winding == FaceWinding::CounterClockwise {
+        indices.reverse();
+    }
+
+    // Start from the canonical vertex
+    if let Some(start) = (0..indices.len())
+        .min_by(|lhs, rhs| lexicographic_cmp(&vertices[indices[*lhs]], &vertices[indices[*rhs]]))
+    {
+        indices.rotate_left(start);
+    }
+
+    indices
+}
+
+fn lexicographic_cmp(lhs: &Vector3, rhs: &Vector3) -> Ordering {
+    lhs.x
+        .total_cmp(&rhs.x)
+        .then_with(|| lhs.y.total_cmp(&rhs.y))
+        .then_with(|| lhs.z.total_cmp(&rhs.z))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn polygon_normal(vertices: &[Vector3], indices: &[usize]) -> Vector3 {
+        let mut normal = Vector3::zeros();
+        for i in 0..indices.len() {
+            let a = vertices[indices[i]];
+            let b = vertices[indices[(i + 1) % indices.len()]];
+            normal += a.cross(&b);
+        }
+        normal.normalize()
+    }
+
+    fn square() -> Vec<Vector3> {
+        vec![
+            Vector3::new(1.0, 1.0, 0.0),
+            Vector3::new(-1.0, -1.0, 0.0),
+            Vector3::new(1.0, -1.0, 0.0),
+            Vector3::new(-1.0, 1.0, 0.0),
+        ]
+    }
+
+    #[test]
+    fn test_winding_matches_normal() {
+        for normal in [Vector3::z(), -Vector3::z()] {
+            let vertices = square();
+            let center = Vector3::zeros();
+
+            let indices = polygon_indices(&vertices, &normal, &center, FaceWinding::Clockwise);
+            assert_eq!(indices.len(), 4);
+            assert!((polygon_normal(&vertices, &indices) - normal).magnitude() < EPSILON);
+
+            let indices =
+                polygon_indices(&vertices, &normal, &center, FaceWinding::CounterClockwise);
+            assert_eq!(indices.len(), 4);
+            assert!((polygon_normal(&vertices, &indices) + normal).magnitude() < EPSILON);
+        }
+    }
+
+    #[test]
+    fn test_canonical_start() {
+        let vertices = square();
+        let mut reversed = vertices.clone();
+        reversed.reverse();
+
+        let normal = Vector3::z();
+        let center = Vector3::zeros();
+
+        let lhs = polygon_indices(&vertices, &normal, &center, FaceWinding::Clockwise);
+        let rhs = polygon_indices(&reversed, &normal, &center, FaceWinding::Clockwise);
+
+        let lhs = lhs.iter().map(|i| vertices[*i]).collect::<Vec<_>>();
+        let rhs = rhs.iter().map(|i| reversed[*i]).collect::<Vec<_>>();
+
+        assert_eq!(lhs, rhs);
+        assert_eq!(lhs[0], Vector3::new(-1.0, -1.0, 0.0));
+    }
+
+    #[test]
+    fn test_duplicate_and_collinear_vertices() {
+        let mut vertices = square();
+        vertices.push(Vector3::new(1.0, 1.0, 0.0));
+        vertices.push(Vector3::new(1.0, 1.0, EPSILON * 0.1));
+        vertices.push(Vector3::new(0.0, -1.0, 0.0));
+        vertices.push(Vector3::new(1.0, 0.5, 0.0));
+
+        let normal = Vector3::z();
+        let center = vertices.iter().sum::<Vector3>() / vertices.len() as f32;
+
+        let indices = polygon_indices(&vertices, &normal, &center, FaceWinding::Clockwise);
+        assert_eq!(indices.len(), 4);
+        assert!(indices.iter().all(|i| *i < 4));
+        assert!((polygon_normal(&vertices, &indices) - normal).magnitude() < EPSILON);
+    }
+
+    #[test]
+    fn test_degenerate_polygons() {
+        let normal = Vector3::z();
+        let center = Vector3::zeros();
+
+        let collinear = vec![
+            Vector3::new(-1.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+        ];
+        let indices = polygon_indices(&collinear, &normal, &center, FaceWinding::Clockwise);
+        assert!(indices.len() < 3);
+
+        let coincident = vec![Vector3::zeros(); 3];
+        let indices = polygon_indices(&coincident, &normal, &center, FaceWinding::Clockwise);
+        assert_eq!(indices, vec![0]);
+
+        let indices = polygon_indices(&[], &normal, &center, FaceWinding::Clockwise);
+        assert!(indices.is_empty());
+    }
+}
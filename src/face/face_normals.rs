@@ -1,10 +1,13 @@
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use usage::Usage;
 
-use crate::Vector3;
+use crate::{brush::BrushId, entity::EntityId, Vector3};
 use std::collections::BTreeMap;
 
-use super::{FaceId, FacePlanes, FaceVertexPlanes, FaceVertices};
+use super::{
+    FaceId, FaceIndices, FacePlanes, FaceSmoothing, FaceVertexPlanes, FaceVertices, NormalMode,
+    Welder,
+};
 
 pub enum FaceNormalsTag {}
 
@@ -88,3 +91,386 @@ pub fn normals_phong_threshold(
         })
         .collect()
 }
+
+/// Generate normals using per-face smoothing settings
+///
+/// Allows hard-edged and smooth geometry to be mixed within a single map.
+pub fn normals_smoothed(
+    face_vertex_planes: &FaceVertexPlanes,
+    face_planes: &FacePlanes,
+    face_smoothing: &FaceSmoothing,
+) -> FaceNormals {
+    face_vertex_planes
+        .par_iter()
+        .map(|(face_id, vertex_planes)| {
+            let smoothing = &face_smoothing[face_id];
+
+            (
+                *face_id,
+                vertex_planes
+                    .par_iter()
+                    .map(|(p0, p1, p2)| {
+                        let normal = *face_planes[p0].normal();
+
+                        let threshold = match smoothing.mode {
+                            NormalMode::Flat => return normal,
+                            NormalMode::PhongAveraged => -1.0,
+                            NormalMode::PhongThreshold(threshold) => {
+                                const ONE_DEGREE: f32 = 0.017_453_3;
+                                ((threshold + 0.01) * ONE_DEGREE).cos()
+                            }
+                        };
+
+                        [p1, p2]
+                            .iter()
+                            .filter(|p| {
+                                let neighbour = &face_smoothing[p];
+                                neighbour.group == smoothing.group
+                                    && neighbour.mode != NormalMode::Flat
+                            })
+                            .map(|p| face_planes[p].normal())
+                            .filter(|n| normal.dot(n) > threshold)
+                            .fold(normal, |acc, n| acc + n)
+                            .normalize()
+                    })
+                    .collect(),
+            )
+        })
+        .collect()
+}
+
+/// Weighting applied to each face's contribution when smoothing across welded vertices
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum NormalWeighting {
+    /// Each face contributes equally
+    Uniform,
+    /// Faces contribute in proportion to their interior angle at the vertex
+    Angle,
+    /// Faces contribute in proportion to their area
+    Area,
+}
+
+/// Average normals over every face in the same entity sharing a welded vertex position
+///
+/// Unlike [`normals_phong_averaged`] and [`normals_phong_threshold`],
+/// this smooths across brush boundaries, removing seams between stacked or wedged brushes.
+/// Each face follows its [`FaceSmoothing`] settings,
+/// only smoothing with faces in the same group that are not themselves flat.
+pub fn normals_phong_welded(
+    entity_brushes: &BTreeMap<EntityId, Vec<BrushId>>,
+    brush_faces: &BTreeMap<BrushId, Vec<FaceId>>,
+    face_vertices: &FaceVertices,
+    face_indices: &FaceIndices,
+    face_planes: &FacePlanes,
+    face_smoothing: &FaceSmoothing,
+    weighting: NormalWeighting,
+) -> FaceNormals {
+    const ONE_DEGREE: f32 = 0.017_453_3;
+
+    // Weld each entity and gather weighted plane normals at each welded position
+    let entities = entity_brushes
+        .par_iter()
+        .map(|(_, brush_ids)| {
+            let face_ids = brush_ids
+                .iter()
+                .flat_map(|brush_id| &brush_faces[brush_id])
+                .copied()
+                .collect::<Vec<_>>();
+
+            let mut welder = Welder::default();
+            let face_welds = face_ids
+                .iter()
+                .map(|face_id| {
+                    face_vertices[face_id]
+                        .iter()
+                        .map(|vertex| welder.weld(vertex))
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+
+            let mut contributions =
+                vec![Vec::<(FaceId, Vector3, f32)>::new(); welder.positions.len()];
+            for (face_id, welds) in face_ids.iter().zip(&face_welds) {
+                let normal = *face_planes[face_id].normal();
+                let vertices = &face_vertices[face_id];
+                let indices = &face_indices[face_id];
+                let area = polygon_area(vertices, indices);
+
+                for (k, i) in indices.iter().enumerate() {
+                    let weight = match weighting {
+                        NormalWeighting::Uniform => 1.0,
+                        NormalWeighting::Area => area,
+                        NormalWeighting::Angle => {
+                            let prev = vertices[indices[(k + indices.len() - 1) % indices.len()]];
+                            let next = vertices[indices[(k + 1) % indices.len()]];
+                            (prev - vertices[*i]).angle(&(next - vertices[*i]))
+                        }
+                    };
+                    contributions[welds[*i]].push((*face_id, normal, weight));
+                }
+            }
+
+            (face_ids.into_iter().zip(face_welds).collect::<Vec<_>>(), contributions)
+        })
+        .collect::<Vec<_>>();
+
+    // Resolve each face's normals independently
+    entities
+        .par_iter()
+        .flat_map(|(faces, contributions)| {
+            faces.par_iter().map(move |(face_id, welds)| {
+                let normal = *face_planes[face_id].normal();
+                let smoothing = &face_smoothing[face_id];
+
+                let threshold = match smoothing.mode {
+                    NormalMode::Flat => return (*face_id, vec![normal; welds.len()]),
+                    NormalMode::PhongAveraged => -1.0,
+                    NormalMode::PhongThreshold(threshold) => {
+                        ((threshold + 0.01) * ONE_DEGREE).cos()
+                    }
+                };
+
+                let normals = welds
+                    .iter()
+                    .map(|weld| {
+                        contributions[*weld]
+                            .iter()
+                            .filter(|(other_id, n, _)| {
+                                let other = &face_smoothing[other_id];
+                                other_id == face_id
+                                    || (other.group == smoothing.group
+                                        && other.mode != NormalMode::Flat
+                                        && normal.dot(n) > threshold)
+                            })
+                            .fold(Vector3::zeros(), |acc, (_, n, weight)| acc + n * *weight)
+                            .try_normalize(0.0)
+                            .unwrap_or(normal)
+                    })
+                    .collect();
+
+                (*face_id, normals)
+            })
+        })
+        .collect()
+}
+
+fn polygon_area(vertices: &[Vector3], indices: &[usize]) -> f32 {
+    if indices.len() < 3 {
+        return 0.0;
+    }
+
+    let v0 = vertices[indices[0]];
+    (1..indices.len() - 1)
+        .map(|i| {
+            let v1 = vertices[indices[i]];
+            let v2 = vertices[indices[i + 1]];
+            (v1 - v0).cross(&(v2 - v0)).magnitude() * 0.5
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        face::Smoothing,
+        generate::{box_brush, cylinder_brush, pipeline, worldspawn, Pipeline},
+        GeoMap,
+    };
+
+    /// Octagonal prism, with walls 45 degrees apart followed by its top and bottom caps
+    fn octagon() -> (GeoMap, FacePlanes, FaceVertices, FaceVertexPlanes) {
+        let Pipeline {
+            geo_map,
+            planes,
+            vertices,
+            vertex_planes,
+            ..
+        } = pipeline(worldspawn(vec![cylinder_brush(
+            Vector3::zeros(),
+            64.0,
+            64.0,
+            8,
+        )]));
+
+        (geo_map, planes, vertices, vertex_planes)
+    }
+
+    /// Angle in degrees between each normal of `face_id` and its plane normal
+    fn deviations(normals: &FaceNormals, planes: &FacePlanes, face_id: FaceId) -> Vec<f32> {
+        normals[&face_id]
+            .iter()
+            .map(|normal| normal.angle(planes[&face_id].normal()).to_degrees())
+            .collect()
+    }
+
+    #[test]
+    fn test_normals_smoothed_threshold() {
+        let (geo_map, planes, _, vertex_planes) = octagon();
+        let wall = FaceId(0);
+
+        let smoothed = |mode| {
+            let smoothing = geo_map
+                .faces
+                .iter()
+                .map(|face_id| (*face_id, Smoothing { mode, group: 0 }))
+                .collect();
+            normals_smoothed(&vertex_planes, &planes, &smoothing)
+        };
+
+        // Flat and sub-45 degree thresholds leave walls unsmoothed
+        for mode in [NormalMode::Flat, NormalMode::PhongThreshold(40.0)].iter() {
+            for deviation in deviations(&smoothed(*mode), &planes, wall) {
+                assert!(deviation < 0.01, "{:?}", mode);
+            }
+        }
+
+        // Walls smooth with each other, but not with the caps, halving the 45 degree corner
+        for deviation in deviations(&smoothed(NormalMode::PhongThreshold(50.0)), &planes, wall) {
+            assert!((deviation - 22.5).abs() < 0.01);
+        }
+
+        // Averaging also pulls in the caps
+        let averaged = smoothed(NormalMode::PhongAveraged);
+        for (normal, deviation) in averaged[&wall]
+            .iter()
+            .zip(deviations(&averaged, &planes, wall))
+        {
+            assert!(deviation > 22.5 + 0.01);
+            assert!(normal.z.abs() > 0.01);
+        }
+    }
+
+    #[test]
+    fn test_normals_smoothed_groups() {
+        let (geo_map, planes, _, vertex_planes) = octagon();
+        let (top, bottom) = (FaceId(8), FaceId(9));
+
+        // Walls in group 0, the top cap in its own group, and a flat bottom cap
+        let smoothing: FaceSmoothing = geo_map
+            .faces
+            .iter()
+            .map(|face_id| {
+                let smoothing = if *face_id == top {
+                    Smoothing {
+                        mode: NormalMode::PhongAveraged,
+                        group: 1,
+                    }
+                } else if *face_id == bottom {
+                    Smoothing {
+                        mode: NormalMode::Flat,
+                        group: 0,
+                    }
+                } else {
+                    Smoothing {
+                        mode: NormalMode::PhongAveraged,
+                        group: 0,
+                    }
+                };
+                (*face_id, smoothing)
+            })
+            .collect();
+
+        let normals = normals_smoothed(&vertex_planes, &planes, &smoothing);
+
+        // Walls ignore the top cap's group and the flat bottom cap
+        for wall in 0..8 {
+            for deviation in deviations(&normals, &planes, FaceId(wall)) {
+                assert!((deviation - 22.5).abs() < 0.01);
+            }
+        }
+
+        // The top cap has no neighbours in its group, and the bottom cap is flat
+        for face_id in [top, bottom].iter() {
+            for deviation in deviations(&normals, &planes, *face_id) {
+                assert!(deviation < 0.01);
+            }
+        }
+    }
+
+    #[test]
+    fn test_normals_phong_welded() {
+        // A pillar of two stacked boxes in one entity
+        let Pipeline {
+            geo_map,
+            planes,
+            vertices,
+            vertex_planes,
+            indices,
+            ..
+        } = pipeline(worldspawn(vec![
+            box_brush(nalgebra::vector![0.0, 0.0, 0.0], nalgebra::vector![64.0, 64.0, 32.0]),
+            box_brush(nalgebra::vector![0.0, 0.0, 32.0], nalgebra::vector![64.0, 64.0, 64.0]),
+        ]));
+
+        let smoothing = |upper_group: u32, mode: NormalMode| -> FaceSmoothing {
+            geo_map
+                .faces
+                .iter()
+                .map(|face_id| {
+                    let group = if face_id.0 >= 6 { upper_group } else { 0 };
+                    (*face_id, Smoothing { mode, group })
+                })
+                .collect()
+        };
+
+        let welded = |smoothing: &FaceSmoothing| {
+            normals_phong_welded(
+                &geo_map.entity_brushes,
+                &geo_map.brush_faces,
+                &vertices,
+                &indices,
+                &planes,
+                smoothing,
+                NormalWeighting::Uniform,
+            )
+        };
+
+        // Normals of the lower box's +X face at its seam with the upper box
+        let lower_x = FaceId(0);
+        let seam = |normals: &FaceNormals| {
+            vertices[&lower_x]
+                .iter()
+                .zip(normals[&lower_x].iter())
+                .filter(|(vertex, _)| (vertex.z - 32.0).abs() < 0.01)
+                .map(|(_, normal)| *normal)
+                .collect::<Vec<_>>()
+        };
+
+        // Per-brush smoothing bends seam normals toward the brush's own top face
+        let per_brush = normals_smoothed(
+            &vertex_planes,
+            &planes,
+            &smoothing(0, NormalMode::PhongAveraged),
+        );
+        for normal in seam(&per_brush) {
+            assert!(normal.z > 0.1);
+        }
+
+        // Smoothing across the seam cancels the coincident top and bottom faces
+        let normals = welded(&smoothing(0, NormalMode::PhongAveraged));
+        assert_eq!(seam(&normals).len(), 2);
+        for normal in seam(&normals) {
+            assert!(normal.z.abs() < 0.01);
+            assert!((normal.x - std::f32::consts::FRAC_1_SQRT_2).abs() < 0.01);
+        }
+
+        // The threshold excludes perpendicular faces
+        for normal in seam(&welded(&smoothing(0, NormalMode::PhongThreshold(45.0)))) {
+            assert!((normal - Vector3::x()).magnitude() < 0.01);
+        }
+
+        // Separate groups don't smooth across the seam
+        for normal in seam(&welded(&smoothing(1, NormalMode::PhongAveraged))) {
+            assert!(normal.z > 0.1);
+        }
+
+        // Flat faces keep their plane normal
+        let flat = welded(&smoothing(0, NormalMode::Flat));
+        for (face_id, normals) in flat.iter() {
+            for normal in normals {
+                assert_eq!(normal, planes[face_id].normal());
+            }
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::BTreeMap;
+
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use usage::Usage;
+
+use super::{FaceBrushes, FaceId};
+use crate::{brush::BrushEntities, entity::entity_property, texture::TextureId, EntityProperties};
+
+/// Normal generation mode for a single face
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NormalMode {
+    /// Use the face plane normal
+    Flat,
+    /// Average with all neighbouring planes in the same smoothing group
+    PhongAveraged,
+    /// Average with neighbouring planes in the same smoothing group within an angular threshold in degrees
+    PhongThreshold(f32),
+}
+
+/// Default `_phong_angle` used by ericw-tools
+pub const DEFAULT_PHONG_ANGLE: f32 = 89.0;
+
+/// Smoothing settings for a single face
+///
+/// Faces only smooth with neighbours that share the same `group` and are not themselves flat.
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+pub struct Smoothing {
+    pub mode: NormalMode,
+    pub group: u32,
+}
+
+pub enum FaceSmoothingTag {}
+
+pub type FaceSmoothing = Usage<FaceSmoothingTag, BTreeMap<FaceId, Smoothing>>;
+
+/// Resolve per-face smoothing settings
+///
+/// Texture overrides take precedence, followed by the owning entity's `_phong` and `_phong_angle` keys,
+/// then `default_mode` in smoothing group 0.
+pub fn face_smoothing(
+    face_brushes: &FaceBrushes,
+    brush_entities: &BrushEntities,
+    entity_properties: &EntityProperties,
+    face_textures: &BTreeMap<FaceId, TextureId>,
+    texture_smoothing: &BTreeMap<TextureId, Smoothing>,
+    default_mode: NormalMode,
+) -> FaceSmoothing {
+    face_brushes
+        .par_iter()
+        .map(|(face_id, brush_id)| {
+            if let Some(smoothing) = texture_smoothing.get(&face_textures[face_id]) {
+                return (*face_id, *smoothing);
+            }
+
+            let properties = &entity_properties[&brush_entities[brush_id]];
+
+            let phong = entity_property(properties, "_phong")
+                .and_then(|phong| phong.trim().parse::<i32>().ok());
+
+            let mode = match phong {
+                Some(0) => NormalMode::Flat,
+                Some(_) => NormalMode::PhongThreshold(
+                    entity_property(properties, "_phong_angle")
+                        .and_then(|angle| angle.trim().parse().ok())
+                        .unwrap_or(DEFAULT_PHONG_ANGLE),
+                ),
+                None => default_mode,
+            };
+
+            (*face_id, Smoothing { mode, group: 0 })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        brush::brush_entities,
+        face::face_brushes,
+        generate::{box_brush, entity},
+        GeoMap,
+    };
+    use shalrath::repr::Map;
+
+    #[test]
+    fn test_face_smoothing() {
+        let cube = |x: f32| {
+            box_brush(
+                nalgebra::vector![x, 0.0, 0.0],
+                nalgebra::vector![x + 32.0, 32.0, 32.0],
+            )
+        };
+
+        // Textured brush whose first face overrides its entity's settings
+        let mut textured = cube(192.0);
+        textured.0[0].texture = "smooth".to_string();
+
+        let geo_map = GeoMap::new(Map(vec![
+            entity(&[("classname", "worldspawn")], vec![cube(0.0), textured]),
+            entity(
+                &[("classname", "func_detail"), ("_phong", "1"), ("_phong_angle", "30")],
+                vec![cube(64.0)],
+            ),
+            entity(&[("classname", "func_detail"), ("_phong", "1")], vec![cube(128.0)]),
+            entity(&[("classname", "func_wall"), ("_phong", "0")], vec![cube(256.0)]),
+        ]));
+
+        let smooth_texture = geo_map
+            .textures
+            .iter()
+            .find(|(_, name)| *name == "smooth")
+            .map(|(texture_id, _)| *texture_id)
+            .unwrap();
+
+        let texture_smoothing = std::iter::once((
+            smooth_texture,
+            Smoothing {
+                mode: NormalMode::PhongAveraged,
+                group: 3,
+            },
+        ))
+        .collect::<BTreeMap<_, _>>();
+
+        let face_brushes = face_brushes(&geo_map.brush_faces);
+        let smoothing = face_smoothing(
+            &face_brushes,
+            &brush_entities(&geo_map.entity_brushes),
+            &geo_map.entity_properties,
+            &geo_map.face_textures,
+            &texture_smoothing,
+            NormalMode::Flat,
+        );
+
+        let brush_smoothing = |brush: usize, face: usize| smoothing[&FaceId(brush * 6 + face)];
+        let group_0 = |mode| Smoothing { mode, group: 0 };
+
+        // Worldspawn has no `_phong`, so falls back to the default
+        assert_eq!(brush_smoothing(0, 0), group_0(NormalMode::Flat));
+
+        // Texture overrides win over the entity settings
+        assert_eq!(
+            brush_smoothing(1, 0),
+            Smoothing {
+                mode: NormalMode::PhongAveraged,
+                group: 3,
+            }
+        );
+        assert_eq!(brush_smoothing(1, 1), group_0(NormalMode::Flat));
+
+        assert_eq!(brush_smoothing(2, 0), group_0(NormalMode::PhongThreshold(30.0)));
+        assert_eq!(
+            brush_smoothing(3, 0),
+            group_0(NormalMode::PhongThreshold(DEFAULT_PHONG_ANGLE))
+        );
+        assert_eq!(brush_smoothing(4, 0), group_0(NormalMode::Flat));
+    }
+}
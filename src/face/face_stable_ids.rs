@@ -0,0 +1,53 @@
+use std::collections::BTreeMap;
+
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use usage::{AsUsage, Usage};
+
+use super::FaceId;
+use crate::{
+    brush::{BrushId, BrushStableIds},
+    stable_id::StableHasher,
+    FacePlanes, StableId,
+};
+
+pub enum FaceStableIdsTag {}
+pub enum StableFacesTag {}
+
+pub type FaceStableIds = Usage<FaceStableIdsTag, BTreeMap<FaceId, StableId>>;
+
+/// Lookup from [`StableId`] to the current [`FaceId`]
+pub type StableFaces = Usage<StableFacesTag, BTreeMap<StableId, FaceId>>;
+
+/// Identify faces by their plane together with the stable ID of their brush
+pub fn face_stable_ids(
+    brush_faces: &BTreeMap<BrushId, Vec<FaceId>>,
+    brush_stable_ids: &BrushStableIds,
+    face_planes: &FacePlanes,
+) -> FaceStableIds {
+    let hashes = brush_faces
+        .par_iter()
+        .flat_map(|(brush_id, face_ids)| {
+            let brush_stable_id = brush_stable_ids[brush_id];
+
+            face_ids.par_iter().map(move |face_id| {
+                let mut hasher = StableHasher::default();
+                hasher.write_u64(brush_stable_id.hash);
+                hasher.write_u64(brush_stable_id.occurrence as u64);
+                for component in face_planes[face_id].quantized() {
+                    hasher.write_i64(component);
+                }
+
+                (*face_id, hasher.finish())
+            })
+        })
+        .collect::<Vec<_>>();
+
+    FaceStableIdsTag::as_usage(crate::stable_id::stable_ids(hashes))
+}
+
+pub fn stable_faces(face_stable_ids: &FaceStableIds) -> StableFaces {
+    face_stable_ids
+        .iter()
+        .map(|(face_id, stable_id)| (*stable_id, *face_id))
+        .collect()
+}
@@ -4,21 +4,39 @@ use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use shalrath::repr::TextureOffset;
 use usage::Usage;
 
+use super::{nonzero_scale, standard_axes};
 use crate::{face::FaceId, vector3_from_texture_plane, FacePlanes, Plane3d, Vector2, Vector3};
 
 // TODO: Replace GeoPlane usage with custom tangent type
 //       (Would storing a basis be viable? No need to conform to godot standards)
 
+/// Texture-space basis of a face
+///
+/// `x` and `y` are unit vectors along which the texture's U and V coordinates increase,
+/// accounting for rotation and flipped scales. `z` is the face normal.
 #[derive(Debug, Default, Copy, Clone, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Basis {
     pub x: Vector3,
     pub y: Vector3,
     pub z: Vector3,
 }
 
+/// Gradients of a face's U and V texel coordinates with respect to world position
+///
+/// `vertex.dot(&gradient.u)` matches the UV projection in texels, up to offset.
+#[derive(Debug, Default, Copy, Clone, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TexelGradient {
+    pub u: Vector3,
+    pub v: Vector3,
+}
+
 pub enum FaceBasesTag {}
+pub enum FaceTexelGradientsTag {}
 
 pub type FaceBases = Usage<FaceBasesTag, BTreeMap<FaceId, Basis>>;
+pub type FaceTexelGradients = Usage<FaceTexelGradientsTag, BTreeMap<FaceId, TexelGradient>>;
 
 pub fn face_bases(
     planes: &Vec<FaceId>,
@@ -43,63 +61,197 @@ pub fn face_bases(
         .collect()
 }
 
+pub fn face_texel_gradients(
+    planes: &Vec<FaceId>,
+    geo_planes: &FacePlanes,
+    face_offsets: &BTreeMap<FaceId, TextureOffset>,
+    face_angles: &BTreeMap<FaceId, f32>,
+    face_scales: &BTreeMap<FaceId, Vector2>,
+) -> FaceTexelGradients {
+    planes
+        .par_iter()
+        .map(|plane_id| {
+            (
+                *plane_id,
+                texel_gradient(
+                    &geo_planes[plane_id],
+                    &face_offsets[plane_id],
+                    face_angles[plane_id],
+                    face_scales[plane_id],
+                ),
+            )
+        })
+        .collect()
+}
+
 fn face_basis(geo_plane: &Plane3d, offset: &TextureOffset, angle: f32, scale: Vector2) -> Basis {
+    let gradient = texel_gradient(geo_plane, offset, angle, scale);
+    let (u_axis, v_axis) = geo_plane.axes();
+
+    Basis {
+        x: gradient.u.try_normalize(0.0).unwrap_or(u_axis),
+        y: gradient.v.try_normalize(0.0).unwrap_or(v_axis),
+        z: *geo_plane.normal(),
+    }
+}
+
+fn texel_gradient(
+    geo_plane: &Plane3d,
+    offset: &TextureOffset,
+    angle: f32,
+    scale: Vector2,
+) -> TexelGradient {
+    let scale = nonzero_scale(scale);
+
     match &offset {
-        shalrath::repr::TextureOffset::Standard { .. } => standard_basis(geo_plane, angle, scale),
-        shalrath::repr::TextureOffset::Valve { .. } => valve_basis(geo_plane, offset),
+        shalrath::repr::TextureOffset::Standard { .. } => {
+            standard_gradient(geo_plane, angle, scale)
+        }
+        shalrath::repr::TextureOffset::Valve { .. } => valve_gradient(offset, scale),
     }
 }
 
-fn standard_basis(plane: &Plane3d, angle: f32, scale: Vector2) -> Basis {
-    let up_vector: &Vector3 = &Vector3::z_axis();
-    let right_vector: &Vector3 = &Vector3::y_axis();
-    let forward_vector: &Vector3 = &Vector3::x_axis();
-
-    let normal = plane.normal();
-
-    let du = normal.dot(up_vector);
-    let dr = normal.dot(right_vector);
-    let df = normal.dot(forward_vector);
-
-    let du_abs = du.abs();
-    let dr_abs = dr.abs();
-    let df_abs = df.abs();
-
-    let du_sign = du.signum();
-    let dr_sign = dr.signum();
-    let df_sign = df.signum();
-
-    let quat = nalgebra::UnitQuaternion::new(normal * -angle.to_radians());
-    if du_abs >= dr_abs && du_abs >= df_abs {
-        let z = *plane.normal() * du_sign;
-        let x = z.cross(forward_vector).normalize();
-        let y = z.cross(right_vector).normalize();
-        Basis { x, y, z }
-    } else if dr_abs >= du_abs && dr_abs >= df_abs {
-        let z = *plane.normal() * dr_sign;
-        let x = z.cross(up_vector).normalize();
-        let y = z.cross(forward_vector).normalize();
-        Basis { x, y, z }
-    } else if df_abs >= du_abs && df_abs >= dr_abs {
-        let z = *plane.normal() * df_sign;
-        let x = z.cross(up_vector).normalize();
-        let y = z.cross(right_vector).normalize();
-        Basis { x, y, z }
-    } else {
-        panic!("Failed to generate basis")
+/// Gradient matching [`standard_uv`](super::standard_uv)
+fn standard_gradient(plane: &Plane3d, angle: f32, scale: Vector2) -> TexelGradient {
+    let (x_axis, y_axis) = standard_axes(plane.normal());
+
+    let (sin, cos) = angle.to_radians().sin_cos();
+
+    TexelGradient {
+        u: (x_axis * cos - y_axis * sin) / scale.x,
+        v: (x_axis * sin + y_axis * cos) / scale.y,
     }
 }
 
-fn valve_basis(plane: &Plane3d, texture_offset: &TextureOffset) -> Basis {
+/// Gradient matching [`valve_uv`](super::valve_uv)
+fn valve_gradient(texture_offset: &TextureOffset, scale: Vector2) -> TexelGradient {
     if let shalrath::repr::TextureOffset::Valve { u, v } = &texture_offset {
-        let u = vector3_from_texture_plane(u);
-        let v = vector3_from_texture_plane(v);
-        Basis {
-            x: u,
-            y: v,
-            z: *plane.normal(),
+        TexelGradient {
+            u: vector3_from_texture_plane(u) / scale.x,
+            v: vector3_from_texture_plane(v) / scale.y,
         }
     } else {
         panic!("Not a valve UV");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use shalrath::repr::TexturePlane;
+
+    use super::*;
+    use crate::face::vertex_uv;
+
+    const TEXTURE_SIZE: Vector2 = nalgebra::vector![64.0, 32.0];
+
+    /// Compare the gradient against finite differences of the UV projection in texels,
+    /// and the basis against the gradient's directions
+    fn assert_basis_matches_uv(plane: Plane3d, offset: TextureOffset, angle: f32, scale: Vector2) {
+        let gradient = texel_gradient(&plane, &offset, angle, scale);
+        let basis = face_basis(&plane, &offset, angle, scale);
+
+        assert!((basis.x - gradient.u.normalize()).magnitude() < 1.0e-5);
+        assert!((basis.y - gradient.v.normalize()).magnitude() < 1.0e-5);
+        assert_eq!(basis.z, plane.n);
+
+        let origin = nalgebra::vector![13.0, -7.0, 5.0];
+        let uv = |p: Vector3| {
+            vertex_uv(p, plane, offset, angle, scale, TEXTURE_SIZE).component_mul(&TEXTURE_SIZE)
+        };
+
+        for step in [Vector3::x(), Vector3::y(), Vector3::z()] {
+            let delta = uv(origin + step) - uv(origin);
+            let expected = nalgebra::vector![step.dot(&gradient.u), step.dot(&gradient.v)];
+            assert!(
+                (delta - expected).magnitude() < 1.0e-3,
+                "{:?} {} {:?}: {:?} != {:?}",
+                plane.n,
+                angle,
+                scale,
+                delta,
+                expected
+            );
+        }
+    }
+
+    fn planes() -> Vec<Plane3d> {
+        [
+            Vector3::x(),
+            -Vector3::x(),
+            Vector3::y(),
+            -Vector3::y(),
+            Vector3::z(),
+            -Vector3::z(),
+            nalgebra::vector![1.0, 1.0, 1.0].normalize(),
+            nalgebra::vector![-0.2, 0.9, -0.4].normalize(),
+        ]
+        .iter()
+        .map(|n| Plane3d { n: *n, d: 4.0 })
+        .collect()
+    }
+
+    fn scales() -> Vec<Vector2> {
+        vec![
+            nalgebra::vector![1.0, 1.0],
+            nalgebra::vector![0.5, 2.0],
+            nalgebra::vector![-1.0, 1.0],
+            nalgebra::vector![1.0, -0.25],
+            nalgebra::vector![-2.0, -3.0],
+        ]
+    }
+
+    #[test]
+    fn test_standard_basis() {
+        let offset = TextureOffset::Standard { u: 8.0, v: -16.0 };
+        for plane in planes() {
+            for angle in [0.0, 15.0, 90.0, -135.0, 270.0] {
+                for scale in scales() {
+                    assert_basis_matches_uv(plane, offset, angle, scale);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_valve_basis() {
+        let offset = TextureOffset::Valve {
+            u: TexturePlane {
+                x: 0.6,
+                y: 0.8,
+                z: 0.0,
+                d: 3.0,
+            },
+            v: TexturePlane {
+                x: 0.0,
+                y: 0.0,
+                z: -1.0,
+                d: -5.0,
+            },
+        };
+        for plane in planes() {
+            for scale in scales() {
+                assert_basis_matches_uv(plane, offset, 30.0, scale);
+            }
+        }
+    }
+
+    #[test]
+    fn test_zero_scale() {
+        let plane = Plane3d {
+            n: Vector3::z(),
+            d: 0.0,
+        };
+        let offset = TextureOffset::Standard { u: 0.0, v: 0.0 };
+
+        // Zero scales are treated as unit scales, matching the UV projection
+        let zero = texel_gradient(&plane, &offset, 0.0, nalgebra::vector![0.0, 0.0]);
+        let unit = texel_gradient(&plane, &offset, 0.0, nalgebra::vector![1.0, 1.0]);
+        assert_eq!(zero, unit);
+
+        assert_basis_matches_uv(plane, offset, 0.0, nalgebra::vector![0.0, 2.0]);
+
+        let basis = face_basis(&plane, &offset, 0.0, nalgebra::vector![0.0, 0.0]);
+        assert!((basis.x.magnitude() - 1.0).abs() < 1.0e-6);
+        assert!((basis.y.magnitude() - 1.0).abs() < 1.0e-6);
+    }
+}
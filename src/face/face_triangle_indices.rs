@@ -3,14 +3,30 @@ use std::collections::BTreeMap;
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use usage::Usage;
 
-use crate::face::FaceId;
+use crate::{face::FaceId, Plane3d, Vector2, Vector3, EPSILON};
 
-use super::FaceIndices;
+use super::{FaceIndices, FacePlanes, FaceVertices};
 
 pub enum FaceTriangleIndicesTag {}
 
 pub type FaceTriangleIndices = Usage<FaceTriangleIndicesTag, BTreeMap<FaceId, Vec<usize>>>;
 
+/// Strategy used to split a face polygon into triangles
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Triangulation {
+    /// Fan out from the first vertex
+    Fan,
+    /// Zig-zag between both ends of the polygon, avoiding a single high-valence vertex
+    Strip,
+    /// Clip ears from the polygon projected onto its face plane, skipping zero-area triangles
+    EarClip,
+    /// Minimize the total edge length of the triangulation
+    MinimumWeight,
+    /// Maximize the smallest interior angle of the triangulation
+    MaximizeMinAngle,
+}
+
 /// Generate triangle indices
 pub fn face_triangle_indices(face_indices: &FaceIndices) -> FaceTriangleIndices {
     face_indices
@@ -26,3 +42,389 @@ pub fn face_triangle_indices(face_indices: &FaceIndices) -> FaceTriangleIndices
         })
         .collect()
 }
+
+/// Generate triangle indices using the specified strategy
+///
+/// Triangles preserve the winding of `face_indices`.
+/// [`Triangulation::MinimumWeight`] and [`Triangulation::MaximizeMinAngle`]
+/// assume convex faces, which holds for all brush geometry.
+pub fn face_triangle_indices_with(
+    face_indices: &FaceIndices,
+    face_vertices: &FaceVertices,
+    face_planes: &FacePlanes,
+    triangulation: Triangulation,
+) -> FaceTriangleIndices {
+    face_indices
+        .par_iter()
+        .filter(|(_, indices)| indices.len() >= 3)
+        .map(|(face_id, indices)| {
+            let vertices = &face_vertices[face_id];
+            let polygon = indices.iter().map(|i| vertices[*i]).collect::<Vec<_>>();
+
+            let triangles = match triangulation {
+                Triangulation::Fan => fan(polygon.len()),
+                Triangulation::Strip => strip(polygon.len()),
+                Triangulation::EarClip => ear_clip(&polygon, &face_planes[face_id]),
+                Triangulation::MinimumWeight => optimal(
+                    &polygon,
+                    |a, b, c| {
+                        if triangle_area(a, b, c) < EPSILON {
+                            DEGENERATE_WEIGHT
+                        } else {
+                            (b - a).magnitude() + (c - b).magnitude() + (a - c).magnitude()
+                        }
+                    },
+                    0.0,
+                    |lhs, rhs| lhs + rhs,
+                    |candidate, best| candidate < best,
+                ),
+                Triangulation::MaximizeMinAngle => optimal(
+                    &polygon,
+                    triangle_min_angle,
+                    f32::INFINITY,
+                    f32::min,
+                    |candidate, best| candidate > best,
+                ),
+            };
+
+            (
+                *face_id,
+                triangles
+                    .into_iter()
+                    .flat_map(|[a, b, c]| [indices[a], indices[b], indices[c]])
+                    .collect(),
+            )
+        })
+        .collect()
+}
+
+/// Cost assigned to zero-area triangles so they are only chosen when unavoidable
+const DEGENERATE_WEIGHT: f32 = 1.0e12;
+
+fn fan(n: usize) -> Vec<[usize; 3]> {
+    (1..n - 1).map(|i| [0, i, i + 1]).collect()
+}
+
+fn strip(n: usize) -> Vec<[usize; 3]> {
+    let mut triangles = Vec::with_capacity(n - 2);
+
+    let mut lo = 0;
+    let mut hi = n - 1;
+    let mut advance_lo = true;
+
+    while hi - lo >= 2 {
+        if advance_lo {
+            triangles.push([lo, lo + 1, hi]);
+            lo += 1;
+        } else {
+            triangles.push([lo, hi - 1, hi]);
+            hi -= 1;
+        }
+        advance_lo = !advance_lo;
+    }
+
+    triangles
+}
+
+fn ear_clip(polygon: &[Vector3], plane: &Plane3d) -> Vec<[usize; 3]> {
+    let points = project(polygon, plane);
+
+    // Orientation of the polygon relative to the projection
+    let orientation = (0..points.len())
+        .map(|i| {
+            let a = points[i];
+            let b = points[(i + 1) % points.len()];
+            a.x * b.y - b.x * a.y
+        })
+        .sum::<f32>()
+        .signum();
+
+    let mut remaining = (0..points.len()).collect::<Vec<_>>();
+    let mut triangles = Vec::with_capacity(points.len() - 2);
+
+    while remaining.len() > 3 {
+        let n = remaining.len();
+
+        let ear = (0..n).find(|i| {
+            let prev = remaining[(i + n - 1) % n];
+            let curr = remaining[*i];
+            let next = remaining[(i + 1) % n];
+
+            // Reject reflex and near-collinear corners, scaling the tolerance by edge length
+            // so it behaves the same for small and large faces
+            let (a, b, c) = (points[prev], points[curr], points[next]);
+            let (ab, bc) = (b - a, c - b);
+            if cross_2d(&ab, &bc) * orientation <= EPSILON * ab.magnitude() * bc.magnitude() {
+                return false;
+            }
+
+            remaining
+                .iter()
+                .filter(|j| **j != prev && **j != curr && **j != next)
+                .all(|j| !point_in_triangle(&points[*j], &a, &b, &c, orientation))
+        });
+
+        // Only collinear or reflex vertices remain, so fan what's left, skipping zero-area triangles
+        let ear = match ear {
+            Some(ear) => ear,
+            None => {
+                triangles.extend(
+                    (1..n - 1)
+                        .map(|i| [remaining[0], remaining[i], remaining[i + 1]])
+                        .filter(|[a, b, c]| {
+                            !is_degenerate(&polygon[*a], &polygon[*b], &polygon[*c])
+                        }),
+                );
+                return triangles;
+            }
+        };
+
+        triangles.push([
+            remaining[(ear + n - 1) % n],
+            remaining[ear],
+            remaining[(ear + 1) % n],
+        ]);
+        remaining.remove(ear);
+    }
+
+    if let [a, b, c] = remaining[..] {
+        if !is_degenerate(&polygon[a], &polygon[b], &polygon[c]) {
+            triangles.push([a, b, c]);
+        }
+    }
+
+    triangles
+}
+
+/// Dynamic programming triangulation of a convex polygon
+///
+/// `combine` folds sub-polygon scores together, and `better` decides whether a candidate beats the current best.
+fn optimal(
+    polygon: &[Vector3],
+    score: impl Fn(&Vector3, &Vector3, &Vector3) -> f32,
+    empty: f32,
+    combine: impl Fn(f32, f32) -> f32,
+    better: impl Fn(f32, f32) -> bool,
+) -> Vec<[usize; 3]> {
+    let n = polygon.len();
+
+    let mut scores = vec![vec![empty; n]; n];
+    let mut splits = vec![vec![0; n]; n];
+
+    for span in 2..n {
+        for i in 0..n - span {
+            let j = i + span;
+
+            let mut best = None;
+            for k in i + 1..j {
+                let candidate = combine(
+                    combine(scores[i][k], scores[k][j]),
+                    score(&polygon[i], &polygon[k], &polygon[j]),
+                );
+
+                match best {
+                    Some(current) if !better(candidate, current) => (),
+                    _ => {
+                        best = Some(candidate);
+                        splits[i][j] = k;
+                    }
+                }
+            }
+
+            scores[i][j] = best.unwrap_or(empty);
+        }
+    }
+
+    let mut triangles = Vec::with_capacity(n - 2);
+    let mut stack = vec![(0, n - 1)];
+    while let Some((i, j)) = stack.pop() {
+        if j - i < 2 {
+            continue;
+        }
+
+        let k = splits[i][j];
+        triangles.push([i, k, j]);
+        stack.push((i, k));
+        stack.push((k, j));
+    }
+
+    triangles
+}
+
+fn project(polygon: &[Vector3], plane: &Plane3d) -> Vec<Vector2> {
+    let (u, v) = plane.axes();
+
+    polygon
+        .iter()
+        .map(|vertex| nalgebra::vector![vertex.dot(&u), vertex.dot(&v)])
+        .collect()
+}
+
+fn cross_2d(a: &Vector2, b: &Vector2) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+fn point_in_triangle(p: &Vector2, a: &Vector2, b: &Vector2, c: &Vector2, orientation: f32) -> bool {
+    let ab = cross_2d(&(b - a), &(p - a)) * orientation;
+    let bc = cross_2d(&(c - b), &(p - b)) * orientation;
+    let ca = cross_2d(&(a - c), &(p - c)) * orientation;
+    ab >= -EPSILON && bc >= -EPSILON && ca >= -EPSILON
+}
+
+fn triangle_area(a: &Vector3, b: &Vector3, c: &Vector3) -> f32 {
+    (b - a).cross(&(c - a)).magnitude() * 0.5
+}
+
+/// Returns true if the triangle's height is within [`EPSILON`] of zero
+fn is_degenerate(a: &Vector3, b: &Vector3, c: &Vector3) -> bool {
+    let longest = (b - a)
+        .magnitude()
+        .max((c - b).magnitude())
+        .max((a - c).magnitude());
+    triangle_area(a, b, c) * 2.0 <= EPSILON * longest
+}
+
+fn triangle_min_angle(a: &Vector3, b: &Vector3, c: &Vector3) -> f32 {
+    let corner = |p: &Vector3, q: &Vector3, r: &Vector3| {
+        let pq = q - p;
+        let pr = r - p;
+        if pq.magnitude() < EPSILON || pr.magnitude() < EPSILON {
+            return 0.0;
+        }
+        pq.angle(&pr)
+    };
+
+    corner(a, b, c).min(corner(b, c, a)).min(corner(c, a, b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STRATEGIES: [Triangulation; 5] = [
+        Triangulation::Fan,
+        Triangulation::Strip,
+        Triangulation::EarClip,
+        Triangulation::MinimumWeight,
+        Triangulation::MaximizeMinAngle,
+    ];
+
+    fn polygon_area(polygon: &[Vector3]) -> f32 {
+        let mut area = Vector3::zeros();
+        for i in 0..polygon.len() {
+            area += polygon[i].cross(&polygon[(i + 1) % polygon.len()]);
+        }
+        area.magnitude() * 0.5
+    }
+
+    /// Triangulate a single face, returning its triangles
+    fn triangulate(polygon: &[Vector3], triangulation: Triangulation) -> Vec<[Vector3; 3]> {
+        let face_id = FaceId(0);
+        let indices: FaceIndices =
+            std::iter::once((face_id, (0..polygon.len()).collect::<Vec<_>>())).collect();
+        let vertices: FaceVertices = std::iter::once((face_id, polygon.to_vec())).collect();
+        let planes: FacePlanes = std::iter::once((
+            face_id,
+            Plane3d {
+                n: Vector3::z(),
+                d: 0.0,
+            },
+        ))
+        .collect();
+
+        let triangles = face_triangle_indices_with(&indices, &vertices, &planes, triangulation);
+        triangles[&face_id]
+            .chunks(3)
+            .map(|t| [polygon[t[0]], polygon[t[1]], polygon[t[2]]])
+            .collect()
+    }
+
+    fn check(polygon: &[Vector3], triangulation: Triangulation) -> Vec<[Vector3; 3]> {
+        let triangles = triangulate(polygon, triangulation);
+
+        let area = triangles
+            .iter()
+            .map(|[a, b, c]| triangle_area(a, b, c))
+            .sum::<f32>();
+        assert!(
+            (area - polygon_area(polygon)).abs() < 0.01,
+            "{:?} area {} != {}",
+            triangulation,
+            area,
+            polygon_area(polygon)
+        );
+
+        // Every triangle keeps the polygon's counter-clockwise winding
+        for [a, b, c] in triangles.iter() {
+            assert!((b - a).cross(&(c - a)).z >= 0.0, "{:?}", triangulation);
+        }
+
+        triangles
+    }
+
+    #[test]
+    fn test_convex_polygon() {
+        let polygon = (0..12)
+            .map(|i| {
+                let theta = i as f32 / 12.0 * std::f32::consts::TAU;
+                nalgebra::vector![theta.cos() * 64.0, theta.sin() * 64.0, 0.0]
+            })
+            .collect::<Vec<_>>();
+
+        for triangulation in STRATEGIES.iter() {
+            let triangles = check(&polygon, *triangulation);
+            assert_eq!(triangles.len(), 10, "{:?}", triangulation);
+        }
+
+        let indices: FaceIndices =
+            std::iter::once((FaceId(0), (0..12).collect::<Vec<_>>())).collect();
+        let expected = (1..11).flat_map(|i| [0, i, i + 1]).collect::<Vec<_>>();
+        assert_eq!(face_triangle_indices(&indices)[&FaceId(0)], expected);
+    }
+
+    #[test]
+    fn test_collinear_vertices() {
+        // Rectangle with a T-junction vertex halfway along its long edges
+        let polygon = vec![
+            nalgebra::vector![0.0, 0.0, 0.0],
+            nalgebra::vector![64.0, 0.0, 0.0],
+            nalgebra::vector![128.0, 0.0, 0.0],
+            nalgebra::vector![128.0, 64.0, 0.0],
+            nalgebra::vector![64.0, 64.0, 0.0],
+            nalgebra::vector![0.0, 64.0, 0.0],
+        ];
+
+        for triangulation in STRATEGIES.iter() {
+            let triangles = check(&polygon, *triangulation);
+
+            match triangulation {
+                // Fixed patterns emit every triangle, including zero-area ones
+                Triangulation::Fan | Triangulation::Strip => assert_eq!(triangles.len(), 4),
+                _ => {
+                    assert_eq!(triangles.len(), 4, "{:?}", triangulation);
+                    assert!(
+                        triangles
+                            .iter()
+                            .all(|[a, b, c]| !is_degenerate(a, b, c)),
+                        "{:?}",
+                        triangulation
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_small_polygon() {
+        // Corners of faces smaller than a unit are still convex relative to their edges
+        let polygon = vec![
+            nalgebra::vector![0.0, 0.0, 0.0],
+            nalgebra::vector![0.02, 0.0, 0.0],
+            nalgebra::vector![0.02, 0.02, 0.0],
+            nalgebra::vector![0.0, 0.02, 0.0],
+        ];
+
+        let triangles = triangulate(&polygon, Triangulation::EarClip);
+        assert_eq!(triangles.len(), 2);
+    }
+}
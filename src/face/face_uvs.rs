@@ -1,6 +1,6 @@
 use crate::{
     texture::{TextureId, TextureSizes},
-    Plane3d, Vector2, Vector3,
+    Diagnostic, Diagnostics, Plane3d, Vector2, Vector3,
 };
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use shalrath::repr::{TextureOffset, TexturePlane};
@@ -13,6 +13,9 @@ pub enum FaceUvsTag {}
 
 pub type FaceUvs = Usage<FaceUvsTag, BTreeMap<FaceId, Vec<Vector2>>>;
 
+/// Texture size used for faces whose texture is missing from [`TextureSizes`]
+pub const DEFAULT_TEXTURE_SIZE: (u32, u32) = (256, 256);
+
 pub fn new(
     faces: &Vec<FaceId>,
     textures: &BTreeMap<TextureId, String>,
@@ -23,17 +26,20 @@ pub fn new(
     face_texture_rotations: &BTreeMap<FaceId, f32>,
     face_texture_scales: &BTreeMap<FaceId, Vector2>,
     texture_sizes: &TextureSizes,
+    diagnostics: &dyn Diagnostics,
 ) -> FaceUvs {
     faces
         .par_iter()
         .map(|face_id| {
             let face_texture = &face_textures[face_id];
             let texture_size = texture_sizes.get(face_texture).copied().unwrap_or_else(|| {
-                println!(
-                    "Warning: Texture {} not found, generating UV with default size of 256x256",
-                    &textures[face_texture],
-                );
-                (256, 256)
+                diagnostics.report(Diagnostic::MissingTexture {
+                    face_id: *face_id,
+                    texture_id: *face_texture,
+                    texture: textures[face_texture].clone(),
+                    default_size: DEFAULT_TEXTURE_SIZE,
+                });
+                DEFAULT_TEXTURE_SIZE
             });
             let face_vertices = &face_vertices[face_id];
             let face_plane = face_planes[face_id];
@@ -69,6 +75,8 @@ pub fn vertex_uv(
     texture_scale: Vector2,
     texture_size: Vector2,
 ) -> Vector2 {
+    let texture_scale = nonzero_scale(texture_scale);
+
     match texture_offset {
         TextureOffset::Standard { u, v } => standard_uv(
             vertex,
@@ -83,6 +91,30 @@ pub fn vertex_uv(
     }
 }
 
+/// Texture scale with zero components replaced by one, as the Quake compilers do
+pub fn nonzero_scale(scale: Vector2) -> Vector2 {
+    scale.map(|component| if component == 0.0 { 1.0 } else { component })
+}
+
+/// World-space axes that Standard-format texturing projects onto before rotation and scale
+///
+/// The face is projected along whichever of Z, Y or X its normal is most aligned with.
+pub fn standard_axes(normal: &Vector3) -> (Vector3, Vector3) {
+    let du = normal.z.abs();
+    let dr = normal.y.abs();
+    let df = normal.x.abs();
+
+    if du >= dr && du >= df {
+        (Vector3::x(), -Vector3::y())
+    } else if dr >= du && dr >= df {
+        (Vector3::x(), -Vector3::z())
+    } else if df >= du && df >= dr {
+        (Vector3::y(), -Vector3::z())
+    } else {
+        panic!("Zero-length normal");
+    }
+}
+
 pub fn standard_uv(
     vertex: Vector3,
     brush_plane: Plane3d,
@@ -92,27 +124,9 @@ pub fn standard_uv(
     texture_scale: Vector2,
     texture_size: Vector2,
 ) -> Vector2 {
-    let up_vector = Vector3::z_axis();
-    let right_vector = Vector3::y_axis();
-    let forward_vector = Vector3::x_axis();
-
-    let du = brush_plane.normal().dot(&up_vector).abs();
-    let dr = brush_plane.normal().dot(&right_vector).abs();
-    let df = brush_plane.normal().dot(&forward_vector).abs();
-
-    let (x, y);
-    if du >= dr && du >= df {
-        x = vertex.x;
-        y = -vertex.y;
-    } else if dr >= du && dr >= df {
-        x = vertex.x;
-        y = -vertex.z;
-    } else if df >= du && df >= dr {
-        x = vertex.y;
-        y = -vertex.z;
-    } else {
-        panic!("Zero-length normal");
-    }
+    let (x_axis, y_axis) = standard_axes(brush_plane.normal());
+    let x = vertex.dot(&x_axis);
+    let y = vertex.dot(&y_axis);
 
     let rot = nalgebra::Rotation2::new(texture_rotation.to_radians());
 
@@ -0,0 +1,275 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use usage::Usage;
+
+use super::{FaceId, FaceNormals, FaceVertices};
+use crate::{
+    brush::{BrushHulls, BrushId},
+    lightmap::{light_contribution, occluded, LightBakeSettings, Lights},
+    Diagnostic, Diagnostics, Plane3d, Vector3,
+};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct VertexLightSettings {
+    /// Number of hemisphere rays traced per vertex for ambient occlusion, or zero to disable
+    pub ao_samples: usize,
+    /// Maximum distance at which geometry occludes a vertex
+    pub ao_distance: f32,
+    /// Blend between no occlusion (0.0) and full occlusion (1.0)
+    pub ao_strength: f32,
+    pub light: LightBakeSettings,
+}
+
+impl Default for VertexLightSettings {
+    fn default() -> Self {
+        VertexLightSettings {
+            ao_samples: 16,
+            ao_distance: 64.0,
+            ao_strength: 1.0,
+            light: LightBakeSettings::default(),
+        }
+    }
+}
+
+pub enum FaceVertexColorsTag {}
+
+/// Per-vertex linear RGB lighting, where 1.0 corresponds to a Quake light level of 255
+pub type FaceVertexColors = Usage<FaceVertexColorsTag, BTreeMap<FaceId, Vec<Vector3>>>;
+
+/// Bake ambient occlusion and direct lighting for each face vertex
+///
+/// Occlusion is sampled with a fixed cosine-weighted spiral over the hemisphere of each vertex normal,
+/// so results are deterministic between runs.
+///
+/// Faces whose normals don't match their vertices one-to-one, such as normals generated before welding,
+/// are reported and omitted.
+pub fn face_vertex_colors(
+    face_vertices: &FaceVertices,
+    face_normals: &FaceNormals,
+    brush_hulls: &BrushHulls,
+    lights: &Lights,
+    sky_brushes: &BTreeSet<BrushId>,
+    settings: &VertexLightSettings,
+    diagnostics: &dyn Diagnostics,
+) -> FaceVertexColors {
+    let hemisphere = hemisphere_directions(settings.ao_samples);
+
+    face_vertices
+        .par_iter()
+        .flat_map(|(face_id, vertices)| {
+            let normals = &face_normals[face_id];
+            if vertices.len() != normals.len() {
+                diagnostics.report(Diagnostic::MismatchedNormals {
+                    face_id: *face_id,
+                    vertex_count: vertices.len(),
+                    normal_count: normals.len(),
+                });
+                return None;
+            }
+
+            Some((
+                *face_id,
+                vertices
+                    .par_iter()
+                    .zip(normals.par_iter())
+                    .map(|(vertex, normal)| {
+                        let light = lights.iter().fold(settings.light.ambient, |acc, light| {
+                            acc + light_contribution(
+                                light,
+                                vertex,
+                                normal,
+                                brush_hulls,
+                                sky_brushes,
+                                &settings.light,
+                            )
+                        });
+
+                        light
+                            * ambient_occlusion(vertex, normal, &hemisphere, brush_hulls, settings)
+                    })
+                    .collect(),
+            ))
+        })
+        .collect()
+}
+
+fn ambient_occlusion(
+    vertex: &Vector3,
+    normal: &Vector3,
+    hemisphere: &[Vector3],
+    brush_hulls: &BrushHulls,
+    settings: &VertexLightSettings,
+) -> f32 {
+    if hemisphere.is_empty() {
+        return 1.0;
+    }
+
+    let (tangent, bitangent) = Plane3d { n: *normal, d: 0.0 }.axes();
+    let origin = vertex + normal * settings.light.surface_offset;
+
+    let occluded_count = hemisphere
+        .iter()
+        .filter(|local| {
+            let direction = tangent * local.x + bitangent * local.y + normal * local.z;
+            occluded(
+                brush_hulls,
+                &BTreeSet::default(),
+                &origin,
+                &direction,
+                settings.ao_distance,
+                false,
+            )
+        })
+        .count();
+
+    let occlusion = occluded_count as f32 / hemisphere.len() as f32;
+    1.0 - occlusion * settings.ao_strength
+}
+
+/// Cosine-weighted directions about +Z distributed along a golden-angle spiral
+fn hemisphere_directions(count: usize) -> Vec<Vector3> {
+    let golden_angle = std::f32::consts::PI * (3.0 - 5.0f32.sqrt());
+
+    (0..count)
+        .map(|i| {
+            let t = (i as f32 + 0.5) / count as f32;
+            let radius = t.sqrt();
+            let phi = i as f32 * golden_angle;
+            nalgebra::vector![radius * phi.cos(), radius * phi.sin(), (1.0 - t).sqrt()]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        face::normals_flat,
+        generate::{box_brush, pipeline, worldspawn},
+        lightmap::{Light, LightFalloff, LightKind},
+        DiagnosticCollector, EPSILON,
+    };
+
+    fn fixture() -> (FaceVertices, FaceNormals, BrushHulls) {
+        // A slab with a box standing on it
+        let pipeline = pipeline(worldspawn(vec![
+            box_brush(nalgebra::vector![0.0, 0.0, 0.0], nalgebra::vector![256.0, 256.0, 16.0]),
+            box_brush(
+                nalgebra::vector![96.0, 96.0, 16.0],
+                nalgebra::vector![160.0, 160.0, 80.0],
+            ),
+        ]));
+
+        let normals = normals_flat(&pipeline.vertices, &pipeline.planes);
+
+        (pipeline.vertices, normals, pipeline.hulls)
+    }
+
+    #[test]
+    fn test_face_vertex_colors() {
+        let (vertices, normals, hulls) = fixture();
+
+        let lights: Lights = std::iter::once(Light {
+            kind: LightKind::Point,
+            position: nalgebra::vector![128.0, 128.0, 200.0],
+            intensity: 255.0,
+            color: Vector3::repeat(1.0),
+            wait: 1.0,
+            falloff: LightFalloff::None,
+        })
+        .collect();
+
+        let settings = VertexLightSettings {
+            ao_samples: 0,
+            ..VertexLightSettings::default()
+        };
+        let colors = face_vertex_colors(
+            &vertices,
+            &normals,
+            &hulls,
+            &lights,
+            &BTreeSet::default(),
+            &settings,
+            &(),
+        );
+
+        let slab_top = FaceId(4);
+        let slab_bottom = FaceId(5);
+        let box_top = FaceId(10);
+
+        assert_eq!(colors.len(), vertices.len());
+        for (face_id, colors) in colors.iter() {
+            assert_eq!(colors.len(), vertices[face_id].len());
+        }
+
+        assert!(colors[&box_top].iter().all(|color| color.x > 0.0));
+        assert!(colors[&slab_top].iter().all(|color| color.x > 0.0));
+        assert!(colors[&slab_bottom].iter().all(|color| *color == Vector3::zeros()));
+    }
+
+    #[test]
+    fn test_ambient_occlusion() {
+        let (vertices, normals, hulls) = fixture();
+
+        let settings = VertexLightSettings {
+            light: LightBakeSettings {
+                ambient: Vector3::repeat(1.0),
+                ..LightBakeSettings::default()
+            },
+            ..VertexLightSettings::default()
+        };
+        let colors = face_vertex_colors(
+            &vertices,
+            &normals,
+            &hulls,
+            &Lights::default(),
+            &BTreeSet::default(),
+            &settings,
+            &(),
+        );
+
+        // Open corners of the slab see the whole sky, while those tucked against the box don't
+        let slab_top = FaceId(4);
+        for (vertex, color) in vertices[&slab_top].iter().zip(colors[&slab_top].iter()) {
+            assert!((color.x - 1.0).abs() < EPSILON, "{:?}", vertex);
+        }
+
+        let box_front = FaceId(9);
+        for (vertex, color) in vertices[&box_front].iter().zip(colors[&box_front].iter()) {
+            if vertex.z < 17.0 {
+                assert!(color.x < 1.0, "{:?}", vertex);
+            }
+        }
+    }
+
+    #[test]
+    fn test_mismatched_normals() {
+        let (vertices, mut normals, hulls) = fixture();
+
+        // One normal short on a single face
+        normals.get_mut(&FaceId(0)).unwrap().pop();
+
+        let diagnostics = DiagnosticCollector::default();
+        let colors = face_vertex_colors(
+            &vertices,
+            &normals,
+            &hulls,
+            &Lights::default(),
+            &BTreeSet::default(),
+            &VertexLightSettings::default(),
+            &diagnostics,
+        );
+
+        assert!(!colors.contains_key(&FaceId(0)));
+        assert_eq!(colors.len(), vertices.len() - 1);
+        assert_eq!(
+            diagnostics.into_inner(),
+            vec![Diagnostic::MismatchedNormals {
+                face_id: FaceId(0),
+                vertex_count: 4,
+                normal_count: 3,
+            }]
+        );
+    }
+}
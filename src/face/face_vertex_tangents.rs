@@ -0,0 +1,255 @@
+use std::collections::{BTreeMap, HashMap};
+
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use usage::Usage;
+
+use super::{FaceId, FaceNormals, FaceTriangleIndices, FaceUvs, FaceVertices, Welder};
+use crate::{brush::BrushId, entity::EntityId, Vector3, Vector4};
+
+pub enum FaceVertexTangentsTag {}
+
+/// Per-vertex tangents with the bitangent sign stored in `w`, as expected by glTF and most engines
+///
+/// The bitangent is reconstructed as `normal.cross(tangent.xyz) * tangent.w`.
+pub type FaceVertexTangents = Usage<FaceVertexTangentsTag, BTreeMap<FaceId, Vec<Vector4>>>;
+
+/// Identity of a vertex for tangent sharing: welded position, normal, UV and UV orientation
+type TangentKey = (usize, [i32; 3], [i64; 2], bool);
+
+/// Generate MikkTSpace-compatible per-vertex tangents
+///
+/// Follows the MikkTSpace scheme: each triangle's UV-derived tangent and bitangent are projected
+/// onto the vertex normal, weighted by the triangle's corner angle, and accumulated across all vertices
+/// in the same entity that share a position, normal, UV and UV orientation,
+/// so smooth surfaces spanning several faces receive continuous tangents.
+///
+/// The sign in `w` is that of `normal.cross(tangent).dot(bitangent)`,
+/// so it holds for either triangle winding and flips for mirrored UVs.
+pub fn face_vertex_tangents(
+    entity_brushes: &BTreeMap<EntityId, Vec<BrushId>>,
+    brush_faces: &BTreeMap<BrushId, Vec<FaceId>>,
+    face_vertices: &FaceVertices,
+    face_normals: &FaceNormals,
+    face_uvs: &FaceUvs,
+    face_triangle_indices: &FaceTriangleIndices,
+) -> FaceVertexTangents {
+    entity_brushes
+        .par_iter()
+        .flat_map(|(_, brush_ids)| {
+            let face_ids = brush_ids
+                .iter()
+                .flat_map(|brush_id| &brush_faces[brush_id])
+                .copied()
+                .collect::<Vec<_>>();
+
+            let mut welder = Welder::default();
+            let mut accumulated = HashMap::<TangentKey, (Vector3, Vector3)>::default();
+            let mut face_keys = BTreeMap::<FaceId, Vec<Option<TangentKey>>>::default();
+
+            for face_id in &face_ids {
+                let vertices = &face_vertices[face_id];
+                let normals = &face_normals[face_id];
+                let uvs = &face_uvs[face_id];
+
+                let welds = vertices
+                    .iter()
+                    .map(|vertex| welder.weld(vertex))
+                    .collect::<Vec<_>>();
+
+                let keys = face_keys
+                    .entry(*face_id)
+                    .or_insert_with(|| vec![None; vertices.len()]);
+
+                let triangles = match face_triangle_indices.get(face_id) {
+                    Some(triangles) => triangles,
+                    None => continue,
+                };
+
+                for triangle in triangles.chunks_exact(3) {
+                    let [i0, i1, i2] = [triangle[0], triangle[1], triangle[2]];
+
+                    let e1 = vertices[i1] - vertices[i0];
+                    let e2 = vertices[i2] - vertices[i0];
+                    let s = uvs[i1] - uvs[i0];
+                    let t = uvs[i2] - uvs[i0];
+
+                    let signed_area = s.x * t.y - s.y * t.x;
+                    let orientation = signed_area > 0.0;
+                    let sign = if orientation { 1.0 } else { -1.0 };
+
+                    // UV gradients, scaled by the sign rather than the inverse area
+                    // as only their directions are accumulated
+                    let tangent = (e1 * t.y - e2 * s.y) * sign;
+                    let bitangent = (e2 * s.x - e1 * t.x) * sign;
+
+                    for (corner, i) in [i0, i1, i2].iter().enumerate() {
+                        let normal = normals[*i];
+                        let prev = vertices[triangle[(corner + 2) % 3]] - vertices[*i];
+                        let next = vertices[triangle[(corner + 1) % 3]] - vertices[*i];
+
+                        let key = (
+                            welds[*i],
+                            quantize_normal(&normal),
+                            quantize_uv(&uvs[*i]),
+                            orientation,
+                        );
+                        keys[*i] = Some(key);
+
+                        let projected = match project(&tangent, &normal) {
+                            Some(projected) => projected,
+                            None => continue,
+                        };
+                        let projected_bitangent =
+                            project(&bitangent, &normal).unwrap_or_else(|| normal.cross(&projected));
+
+                        let angle = match (project(&prev, &normal), project(&next, &normal)) {
+                            (Some(prev), Some(next)) => prev.angle(&next),
+                            _ => 0.0,
+                        };
+
+                        let (tangents, bitangents) = accumulated
+                            .entry(key)
+                            .or_insert_with(|| (Vector3::zeros(), Vector3::zeros()));
+                        *tangents += projected * angle;
+                        *bitangents += projected_bitangent * angle;
+                    }
+                }
+            }
+
+            face_ids
+                .into_iter()
+                .map(|face_id| {
+                    let normals = &face_normals[&face_id];
+
+                    let tangents = face_keys[&face_id]
+                        .iter()
+                        .zip(normals.iter())
+                        .map(|(key, normal)| {
+                            let sums = key.and_then(|key| accumulated.get(&key));
+
+                            let tangent = sums
+                                .and_then(|(tangent, _)| tangent.try_normalize(0.0))
+                                .unwrap_or_else(|| fallback_tangent(normal));
+
+                            let sign = match sums {
+                                Some((_, bitangent))
+                                    if normal.cross(&tangent).dot(bitangent) < 0.0 =>
+                                {
+                                    -1.0
+                                }
+                                _ => 1.0,
+                            };
+
+                            nalgebra::vector![tangent.x, tangent.y, tangent.z, sign]
+                        })
+                        .collect();
+
+                    (face_id, tangents)
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Project a vector onto the plane perpendicular to `normal` and normalize it
+fn project(v: &Vector3, normal: &Vector3) -> Option<Vector3> {
+    (v - normal * normal.dot(v)).try_normalize(f32::EPSILON)
+}
+
+fn fallback_tangent(normal: &Vector3) -> Vector3 {
+    crate::Plane3d { n: *normal, d: 0.0 }.axes().0
+}
+
+fn quantize_normal(normal: &Vector3) -> [i32; 3] {
+    const SCALE: f32 = 1000.0;
+    [
+        (normal.x * SCALE).round() as i32,
+        (normal.y * SCALE).round() as i32,
+        (normal.z * SCALE).round() as i32,
+    ]
+}
+
+fn quantize_uv(uv: &crate::Vector2) -> [i64; 2] {
+    const SCALE: f32 = 100_000.0;
+    [(uv.x * SCALE).round() as i64, (uv.y * SCALE).round() as i64]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Vector2;
+
+    /// Tangents of a unit quad on the XY plane facing +Z, with the given triangles and UVs
+    fn quad_tangents(triangles: Vec<usize>, uvs: Vec<Vector2>) -> Vec<Vector4> {
+        let face_id = FaceId(0);
+        let brush_id = BrushId(0);
+
+        let entity_brushes = std::iter::once((EntityId(0), vec![brush_id])).collect();
+        let brush_faces = std::iter::once((brush_id, vec![face_id])).collect();
+
+        let vertices: FaceVertices = std::iter::once((
+            face_id,
+            vec![
+                nalgebra::vector![0.0, 0.0, 0.0],
+                nalgebra::vector![1.0, 0.0, 0.0],
+                nalgebra::vector![1.0, 1.0, 0.0],
+                nalgebra::vector![0.0, 1.0, 0.0],
+            ],
+        ))
+        .collect();
+        let normals: FaceNormals = std::iter::once((face_id, vec![Vector3::z(); 4])).collect();
+        let uvs: FaceUvs = std::iter::once((face_id, uvs)).collect();
+        let triangles: FaceTriangleIndices = std::iter::once((face_id, triangles)).collect();
+
+        let tangents = face_vertex_tangents(
+            &entity_brushes,
+            &brush_faces,
+            &vertices,
+            &normals,
+            &uvs,
+            &triangles,
+        );
+        tangents[&face_id].clone()
+    }
+
+    fn uvs(u_scale: f32) -> Vec<Vector2> {
+        vec![
+            nalgebra::vector![0.0, 0.0],
+            nalgebra::vector![u_scale, 0.0],
+            nalgebra::vector![u_scale, 1.0],
+            nalgebra::vector![0.0, 1.0],
+        ]
+    }
+
+    /// Bitangent as reconstructed by consumers of the tangent table
+    fn bitangent(tangent: &Vector4) -> Vector3 {
+        Vector3::z().cross(&tangent.xyz()) * tangent.w
+    }
+
+    #[test]
+    fn test_winding() {
+        let counter_clockwise = quad_tangents(vec![0, 1, 2, 0, 2, 3], uvs(1.0));
+        let clockwise = quad_tangents(vec![0, 2, 1, 0, 3, 2], uvs(1.0));
+
+        // Tangents follow +U and reconstructed bitangents +V regardless of winding
+        for tangent in counter_clockwise.iter().chain(clockwise.iter()) {
+            assert!((tangent.xyz() - Vector3::x()).magnitude() < 0.001);
+            assert_eq!(tangent.w, 1.0);
+            assert!((bitangent(tangent) - Vector3::y()).magnitude() < 0.001);
+        }
+    }
+
+    #[test]
+    fn test_mirrored_uvs() {
+        for triangles in [vec![0, 1, 2, 0, 2, 3], vec![0, 2, 1, 0, 3, 2]].iter() {
+            let tangents = quad_tangents(triangles.clone(), uvs(-1.0));
+
+            // U runs along -X while V still runs along +Y, so the bitangent sign flips
+            for tangent in &tangents {
+                assert!((tangent.xyz() + Vector3::x()).magnitude() < 0.001);
+                assert_eq!(tangent.w, -1.0);
+                assert!((bitangent(tangent) - Vector3::y()).magnitude() < 0.001);
+            }
+        }
+    }
+}
@@ -6,7 +6,7 @@ use usage::Usage;
 use crate::{
     brush::{BrushHulls, BrushId},
     face::FaceId,
-    ConvexHull, FacePlanes, Plane3d, Vector3, EPSILON,
+    ConvexHull, Diagnostic, Diagnostics, FacePlanes, Plane3d, Vector3, EPSILON,
 };
 
 pub enum FaceVerticesTag {}
@@ -20,8 +20,9 @@ pub fn face_vertices(
     brush_planes: &BTreeMap<BrushId, Vec<FaceId>>,
     face_planes: &FacePlanes,
     brush_hulls: &BrushHulls,
+    diagnostics: &dyn Diagnostics,
 ) -> (FaceVertices, FaceVertexPlanes) {
-    brush_planes
+    let (face_vertices, face_vertex_planes): (FaceVertices, FaceVertexPlanes) = brush_planes
         .par_iter()
         .flat_map(|(brush_id, face_ids)| {
             let hull = &brush_hulls[brush_id];
@@ -55,11 +56,46 @@ pub fn face_vertices(
                     .flatten()
                     .collect::<Vec<_>>();
                 verts.dedup_by(|(_, lhs), (_, rhs)| lhs == rhs);
-                let (vert_planes, verts) = verts.into_iter().unzip();
+
+                let (vert_planes, verts): (Vec<_>, Vec<_>) = verts.into_iter().unzip();
+
+                let vertex_count = distinct_vertex_count(&verts);
+                if vertex_count < 3 {
+                    diagnostics.report(Diagnostic::DegenerateFace {
+                        brush_id: *brush_id,
+                        face_id: *face_id,
+                        vertex_count,
+                    });
+                }
+
                 ((*face_id, verts), (*face_id, vert_planes))
             })
         })
-        .unzip()
+        .unzip();
+
+    // Report brushes that failed to produce any usable faces
+    for (brush_id, face_ids) in brush_planes {
+        if face_ids
+            .iter()
+            .all(|face_id| distinct_vertex_count(&face_vertices[face_id]) < 3)
+        {
+            diagnostics.report(Diagnostic::SkippedBrush {
+                brush_id: *brush_id,
+            });
+        }
+    }
+
+    (face_vertices, face_vertex_planes)
+}
+
+/// Number of vertices at least [`EPSILON`] apart from one another
+///
+/// Vertices shared by more than three planes are produced once per plane triple,
+/// and those duplicates need not be adjacent.
+fn distinct_vertex_count(vertices: &[Vector3]) -> usize {
+    (0..vertices.len())
+        .filter(|i| !(0..*i).any(|j| (vertices[*i] - vertices[j]).magnitude() < EPSILON))
+        .count()
 }
 
 pub fn triplanar_intersection(p0: &Plane3d, p1: &Plane3d, p2: &Plane3d) -> Option<Vector3> {
@@ -80,3 +116,67 @@ pub fn triplanar_intersection(p0: &Plane3d, p1: &Plane3d, p2: &Plane3d) -> Optio
             / denom,
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        brush::brush_hulls,
+        face::face_planes,
+        generate::{box_brush, brush_from_planes, worldspawn},
+        DiagnosticCollector, GeoMap,
+    };
+
+    fn collect(brushes: Vec<shalrath::repr::Brush>) -> Vec<Diagnostic> {
+        let geo_map = GeoMap::new(worldspawn(brushes));
+        let planes = face_planes(&geo_map.face_planes);
+        let hulls = brush_hulls(&geo_map.brush_faces, &planes);
+
+        let diagnostics = DiagnosticCollector::default();
+        face_vertices(&geo_map.brush_faces, &planes, &hulls, &diagnostics);
+        diagnostics.into_inner()
+    }
+
+    #[test]
+    fn test_distinct_vertex_count() {
+        let a = nalgebra::vector![0.0, 0.0, 0.0];
+        let b = nalgebra::vector![1.0, 0.0, 0.0];
+        let c = nalgebra::vector![0.0, 1.0, 0.0];
+
+        assert_eq!(distinct_vertex_count(&[a, b, c]), 3);
+        assert_eq!(distinct_vertex_count(&[a, b, a, b]), 2);
+        assert_eq!(distinct_vertex_count(&[a, b, a + Vector3::z() * EPSILON * 0.5]), 2);
+    }
+
+    #[test]
+    fn test_valid_brush_reports_nothing() {
+        let diagnostics = collect(vec![box_brush(
+            nalgebra::vector![0.0, 0.0, 0.0],
+            nalgebra::vector![64.0, 64.0, 64.0],
+        )]);
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_degenerate_face() {
+        // A pyramid whose apex lies on a redundant fifth side plane,
+        // so that face meets the brush at a single point produced by several plane triples
+        let diagnostics = collect(vec![brush_from_planes([
+            (nalgebra::vector![1.0, 0.0, 1.0], 0.0),
+            (nalgebra::vector![-1.0, 0.0, 1.0], 0.0),
+            (nalgebra::vector![0.0, 1.0, 1.0], 0.0),
+            (nalgebra::vector![0.0, -1.0, 1.0], 0.0),
+            (nalgebra::vector![1.0, 1.0, 4.0], 0.0),
+            (-Vector3::z(), 64.0),
+        ])]);
+
+        assert_eq!(
+            diagnostics,
+            vec![Diagnostic::DegenerateFace {
+                brush_id: BrushId(0),
+                face_id: FaceId(4),
+                vertex_count: 1,
+            }]
+        );
+    }
+}
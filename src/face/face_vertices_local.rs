@@ -0,0 +1,31 @@
+use std::collections::BTreeMap;
+
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+use super::{FaceBrushes, FaceVertices};
+use crate::{brush::BrushEntities, entity::EntityId, Vector3};
+
+/// Re-express face vertices relative to the origin of their owning entity
+///
+/// Accepts any per-entity origin table, such as `EntityOrigins` or `EntityPivots`.
+/// Faces belonging to entities without an origin are copied unchanged.
+pub fn face_vertices_local(
+    face_vertices: &FaceVertices,
+    face_brushes: &FaceBrushes,
+    brush_entities: &BrushEntities,
+    entity_origins: &BTreeMap<EntityId, Vector3>,
+) -> FaceVertices {
+    face_vertices
+        .par_iter()
+        .map(|(face_id, vertices)| {
+            let entity_id = &brush_entities[&face_brushes[face_id]];
+
+            let vertices = match entity_origins.get(entity_id) {
+                Some(origin) => vertices.iter().map(|vertex| vertex - origin).collect(),
+                None => vertices.clone(),
+            };
+
+            (*face_id, vertices)
+        })
+        .collect()
+}
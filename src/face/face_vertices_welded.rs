@@ -0,0 +1,438 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use usage::Usage;
+
+use super::{FaceId, FaceIndices, FaceNormals, FaceUvs, FaceVertexPlanes, FaceVertices};
+use crate::{brush::BrushId, entity::EntityId, Vector3, EPSILON};
+
+/// Size of the grid cells used to find welded vertices along an edge
+const EDGE_CELL: f32 = 32.0;
+
+/// Where a welded vertex came from in its unwelded face
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum VertexSource {
+    /// The vertex at this index of the unwelded face
+    Original(usize),
+    /// A vertex inserted at `t` along the unwelded edge from vertex `a` to vertex `b`
+    Edge { a: usize, b: usize, t: f32 },
+}
+
+pub enum FaceVertexSourcesTag {}
+
+/// Source of each welded vertex, used to carry per-vertex tables over to the welded faces
+pub type FaceVertexSources = Usage<FaceVertexSourcesTag, BTreeMap<FaceId, Vec<VertexSource>>>;
+
+/// Weld coincident vertices and repair T-junctions between the faces of each entity
+///
+/// Vertices within [`EPSILON`] of one another are snapped to a shared position,
+/// and any welded vertex lying along the interior of a face's edge is inserted into that edge.
+///
+/// Existing vertices keep their original indices, with inserted vertices appended.
+/// Per-vertex tables such as [`FaceUvs`] can be carried over with the returned [`FaceVertexSources`]
+/// via [`face_uvs_welded`], [`normals_welded`] and [`face_vertex_planes_welded`].
+pub fn face_vertices_welded(
+    entity_brushes: &BTreeMap<EntityId, Vec<BrushId>>,
+    brush_faces: &BTreeMap<BrushId, Vec<FaceId>>,
+    face_vertices: &FaceVertices,
+    face_indices: &FaceIndices,
+) -> (FaceVertices, FaceIndices, FaceVertexSources) {
+    let faces = entity_brushes
+        .par_iter()
+        .flat_map(|(_, brush_ids)| {
+            let face_ids = brush_ids
+                .iter()
+                .flat_map(|brush_id| &brush_faces[brush_id])
+                .copied()
+                .collect::<Vec<_>>();
+
+            // Snap each vertex to a shared welded position
+            let mut welder = Welder::default();
+            let face_welds = face_ids
+                .iter()
+                .map(|face_id| {
+                    face_vertices[face_id]
+                        .iter()
+                        .map(|vertex| welder.weld(vertex))
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+
+            let welded = welder.positions;
+
+            // Bucket welded vertices into a coarse grid to find candidates along each edge
+            let mut grid = HashMap::<[i64; 3], Vec<usize>>::default();
+            for (i, vertex) in welded.iter().enumerate() {
+                grid.entry(edge_cell(vertex)).or_default().push(i);
+            }
+
+            face_ids
+                .into_iter()
+                .zip(face_welds)
+                .collect::<Vec<_>>()
+                .into_par_iter()
+                .map(|(face_id, welds)| {
+                    let vertices = welds.iter().map(|i| welded[*i]).collect::<Vec<_>>();
+                    let indices = &face_indices[&face_id];
+
+                    let mut out_sources = (0..vertices.len())
+                        .map(VertexSource::Original)
+                        .collect::<Vec<_>>();
+
+                    if indices.len() < 3 {
+                        return (face_id, vertices, indices.clone(), out_sources);
+                    }
+
+                    let own = welds.iter().copied().collect::<BTreeSet<_>>();
+
+                    let mut out_vertices = vertices.clone();
+                    let mut out_indices = Vec::with_capacity(indices.len());
+
+                    for (i, a) in indices.iter().enumerate() {
+                        let b = indices[(i + 1) % indices.len()];
+                        out_indices.push(*a);
+
+                        let va = vertices[*a];
+                        let vb = vertices[b];
+
+                        for (t, weld) in edge_vertices(&welded, &grid, &own, &va, &vb) {
+                            out_indices.push(out_vertices.len());
+                            out_vertices.push(welded[weld]);
+                            out_sources.push(VertexSource::Edge { a: *a, b, t });
+                        }
+                    }
+
+                    (face_id, out_vertices, out_indices, out_sources)
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let mut out_vertices = BTreeMap::new();
+    let mut out_indices = BTreeMap::new();
+    let mut out_sources = BTreeMap::new();
+    for (face_id, vertices, indices, sources) in faces {
+        out_vertices.insert(face_id, vertices);
+        out_indices.insert(face_id, indices);
+        out_sources.insert(face_id, sources);
+    }
+
+    (
+        out_vertices.into_iter().collect(),
+        out_indices.into_iter().collect(),
+        out_sources.into_iter().collect(),
+    )
+}
+
+/// Carry UVs over to welded faces, interpolating along the edge for inserted vertices
+pub fn face_uvs_welded(face_uvs: &FaceUvs, face_vertex_sources: &FaceVertexSources) -> FaceUvs {
+    remap(face_uvs, face_vertex_sources, |a, b, t| a.lerp(b, t))
+        .into_iter()
+        .collect()
+}
+
+/// Carry normals over to welded faces, interpolating along the edge for inserted vertices
+pub fn normals_welded(
+    face_normals: &FaceNormals,
+    face_vertex_sources: &FaceVertexSources,
+) -> FaceNormals {
+    remap(face_normals, face_vertex_sources, |a, b, t| {
+        a.lerp(b, t).try_normalize(0.0).unwrap_or(*a)
+    })
+    .into_iter()
+    .collect()
+}
+
+/// Carry vertex planes over to welded faces
+///
+/// An inserted vertex lies on the edge between its endpoints,
+/// so it takes the face plane and the plane the endpoints share across that edge.
+pub fn face_vertex_planes_welded(
+    face_vertex_planes: &FaceVertexPlanes,
+    face_vertex_sources: &FaceVertexSources,
+) -> FaceVertexPlanes {
+    remap(face_vertex_planes, face_vertex_sources, |a, b, _| {
+        let (face, a1, a2) = *a;
+        let (_, b1, b2) = *b;
+        let edge = [a1, a2]
+            .iter()
+            .copied()
+            .find(|plane| *plane == b1 || *plane == b2)
+            .unwrap_or(a1);
+        (face, edge, edge)
+    })
+    .into_iter()
+    .collect()
+}
+
+fn remap<T, F>(
+    values: &BTreeMap<FaceId, Vec<T>>,
+    face_vertex_sources: &FaceVertexSources,
+    edge: F,
+) -> Vec<(FaceId, Vec<T>)>
+where
+    T: Copy + Send + Sync,
+    F: Fn(&T, &T, f32) -> T + Sync,
+{
+    values
+        .par_iter()
+        .map(|(face_id, values)| {
+            let values = face_vertex_sources[face_id]
+                .iter()
+                .map(|source| match source {
+                    VertexSource::Original(i) => values[*i],
+                    VertexSource::Edge { a, b, t } => edge(&values[*a], &values[*b], *t),
+                })
+                .collect();
+            (*face_id, values)
+        })
+        .collect()
+}
+
+fn edge_cell(vertex: &Vector3) -> [i64; 3] {
+    [
+        (vertex.x / EDGE_CELL).floor() as i64,
+        (vertex.y / EDGE_CELL).floor() as i64,
+        (vertex.z / EDGE_CELL).floor() as i64,
+    ]
+}
+
+/// Find welded vertices strictly inside the segment `va -> vb`, ordered from `va`
+///
+/// Returns each vertex's index and its parameter along the segment.
+fn edge_vertices(
+    welded: &[Vector3],
+    grid: &HashMap<[i64; 3], Vec<usize>>,
+    exclude: &BTreeSet<usize>,
+    va: &Vector3,
+    vb: &Vector3,
+) -> Vec<(f32, usize)> {
+    let ab = vb - va;
+    let length_squared = ab.magnitude_squared();
+    if length_squared < EPSILON * EPSILON {
+        return vec![];
+    }
+    let length = length_squared.sqrt();
+
+    // Walk the edge in steps no longer than a cell,
+    // visiting the cells overlapped by each padded step
+    let steps = (length / EDGE_CELL).ceil().max(1.0) as usize;
+    let mut cells = BTreeSet::new();
+    for step in 0..steps {
+        let p0 = va + ab * (step as f32 / steps as f32);
+        let p1 = va + ab * ((step + 1) as f32 / steps as f32);
+        let min = edge_cell(&p0.inf(&p1).add_scalar(-EPSILON));
+        let max = edge_cell(&p0.sup(&p1).add_scalar(EPSILON));
+
+        for x in min[0]..=max[0] {
+            for y in min[1]..=max[1] {
+                for z in min[2]..=max[2] {
+                    cells.insert([x, y, z]);
+                }
+            }
+        }
+    }
+
+    let t_epsilon = EPSILON / length;
+
+    let mut candidates = cells
+        .iter()
+        .flat_map(|cell| grid.get(cell))
+        .flatten()
+        .filter(|i| !exclude.contains(*i))
+        .flat_map(|i| {
+            let point = &welded[*i];
+            let t = (point - va).dot(&ab) / length_squared;
+            let distance = (point - (va + ab * t)).magnitude();
+
+            if t > t_epsilon && t < 1.0 - t_epsilon && distance < EPSILON {
+                Some((t, *i))
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    candidates.sort_unstable_by(|(lhs, _), (rhs, _)| lhs.total_cmp(rhs));
+    candidates
+}
+
+/// Spatial hash used to merge vertices within [`EPSILON`] of one another
+#[derive(Default)]
+pub(crate) struct Welder {
+    pub(crate) positions: Vec<Vector3>,
+    cells: HashMap<[i64; 3], Vec<usize>>,
+}
+
+impl Welder {
+    fn cell(vertex: &Vector3) -> [i64; 3] {
+        [
+            (vertex.x / EPSILON).floor() as i64,
+            (vertex.y / EPSILON).floor() as i64,
+            (vertex.z / EPSILON).floor() as i64,
+        ]
+    }
+
+    /// Returns the index of the welded position for `vertex`, inserting it if none is within range
+    pub(crate) fn weld(&mut self, vertex: &Vector3) -> usize {
+        let [x, y, z] = Self::cell(vertex);
+
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    if let Some(candidates) = self.cells.get(&[x + dx, y + dy, z + dz]) {
+                        for i in candidates {
+                            if (self.positions[*i] - vertex).magnitude() < EPSILON {
+                                return *i;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        let i = self.positions.len();
+        self.positions.push(*vertex);
+        self.cells.entry([x, y, z]).or_default().push(i);
+        i
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        face,
+        generate::{box_brush, pipeline, worldspawn, Pipeline},
+        texture::TextureSizes,
+    };
+
+    fn welded(
+        map: shalrath::repr::Map,
+    ) -> (Pipeline, (FaceVertices, FaceIndices, FaceVertexSources)) {
+        let pipeline = pipeline(map);
+        let welded = face_vertices_welded(
+            &pipeline.geo_map.entity_brushes,
+            &pipeline.geo_map.brush_faces,
+            &pipeline.vertices,
+            &pipeline.indices,
+        );
+        (pipeline, welded)
+    }
+
+    #[test]
+    fn test_weld_coincident_vertices() {
+        let (pipeline, (welded, indices, sources)) = welded(worldspawn(vec![
+            box_brush(nalgebra::vector![0.0, 0.0, 0.0], nalgebra::vector![64.0, 64.0, 64.0]),
+            box_brush(
+                nalgebra::vector![64.0005, 0.0, 0.0],
+                nalgebra::vector![128.0, 64.0, 64.0],
+            ),
+        ]));
+        let Pipeline {
+            geo_map, vertices, ..
+        } = pipeline;
+
+        // The touching +X and -X faces
+        let lhs = &welded[&FaceId(0)];
+        let rhs = &welded[&FaceId(7)];
+        assert!(rhs.iter().all(|vertex| lhs.contains(vertex)));
+
+        // Matching faces gain no vertices
+        for face_id in geo_map.faces.iter() {
+            assert_eq!(welded[face_id].len(), vertices[face_id].len());
+            assert_eq!(indices[face_id].len(), 4);
+            assert!(sources[face_id]
+                .iter()
+                .all(|source| matches!(source, VertexSource::Original(_))));
+        }
+    }
+
+    #[test]
+    fn test_repair_t_junctions() {
+        // Two boxes resting side by side on top of one twice their width
+        let (pipeline, (welded, indices, sources)) = welded(worldspawn(vec![
+            box_brush(nalgebra::vector![0.0, 0.0, 0.0], nalgebra::vector![128.0, 64.0, 16.0]),
+            box_brush(nalgebra::vector![0.0, 0.0, 16.0], nalgebra::vector![64.0, 64.0, 32.0]),
+            box_brush(nalgebra::vector![64.0, 0.0, 16.0], nalgebra::vector![128.0, 64.0, 32.0]),
+        ]));
+        let Pipeline {
+            geo_map,
+            planes,
+            vertices,
+            vertex_planes,
+            ..
+        } = pipeline;
+
+        let large_top = FaceId(4);
+        let large_front = FaceId(3);
+        let large_back = FaceId(2);
+
+        assert_eq!(welded[&large_top].len(), 6);
+        assert_eq!(indices[&large_top].len(), 6);
+        for face_id in [large_front, large_back].iter() {
+            assert_eq!(welded[face_id].len(), 5);
+            assert_eq!(indices[face_id].len(), 5);
+        }
+
+        for vertex in [
+            nalgebra::vector![64.0, 0.0, 16.0],
+            nalgebra::vector![64.0, 64.0, 16.0],
+        ]
+        .iter()
+        {
+            assert!(welded[&large_top]
+                .iter()
+                .any(|rhs| (rhs - vertex).magnitude() < EPSILON));
+        }
+
+        // Small faces and the rest of the large box are untouched
+        for face_id in geo_map.faces.iter() {
+            if ![large_top, large_front, large_back].contains(face_id) {
+                assert_eq!(welded[face_id].len(), vertices[face_id].len());
+            }
+        }
+
+        // Inserted vertices lie halfway along their edge
+        for source in sources[&large_top].iter().skip(4) {
+            match source {
+                VertexSource::Edge { t, .. } => assert!((t - 0.5).abs() < EPSILON),
+                VertexSource::Original(_) => panic!("expected an inserted vertex"),
+            }
+        }
+
+        // Carried over UVs match UVs generated from the welded vertices
+        let uvs = |vertices: &FaceVertices| {
+            face::new(
+                &geo_map.faces,
+                &geo_map.textures,
+                &geo_map.face_textures,
+                vertices,
+                &planes,
+                &geo_map.face_offsets,
+                &geo_map.face_angles,
+                &geo_map.face_scales,
+                &TextureSizes::default(),
+                &(),
+            )
+        };
+
+        let carried = face_uvs_welded(&uvs(&vertices), &sources);
+        let generated = uvs(&welded);
+        for face_id in geo_map.faces.iter() {
+            assert_eq!(carried[face_id].len(), welded[face_id].len());
+            for (lhs, rhs) in carried[face_id].iter().zip(generated[face_id].iter()) {
+                assert!((lhs - rhs).magnitude() < EPSILON);
+            }
+        }
+
+        // Inserted vertices take the face plane and the shared edge plane
+        let welded_planes = face_vertex_planes_welded(&vertex_planes, &sources);
+        assert_eq!(welded_planes[&large_top].len(), 6);
+        for (face, p1, p2) in welded_planes[&large_top].iter().skip(4) {
+            assert_eq!(*face, large_top);
+            assert_eq!(p1, p2);
+            assert!([large_front, large_back].contains(p1));
+        }
+    }
+}
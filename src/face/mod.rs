@@ -1,4 +1,5 @@
 mod face_centers;
+mod face_coverage;
 mod face_face_containment;
 mod face_duplicates;
 mod face_indices;
@@ -12,8 +13,15 @@ mod face_normals;
 mod face_brushes;
 mod face_lines;
 mod interior_faces;
+mod face_vertices_local;
+mod face_vertices_welded;
+mod face_vertex_colors;
+mod face_smoothing;
+mod face_vertex_tangents;
+mod face_stable_ids;
 
 pub use face_centers::*;
+pub use face_coverage::*;
 pub use face_face_containment::*;
 pub use face_duplicates::*;
 pub use face_indices::*;
@@ -27,3 +35,9 @@ pub use face_normals::*;
 pub use face_brushes::*;
 pub use face_lines::*;
 pub use interior_faces::*;
+pub use face_vertices_local::*;
+pub use face_vertices_welded::*;
+pub use face_vertex_colors::*;
+pub use face_smoothing::*;
+pub use face_vertex_tangents::*;
+pub use face_stable_ids::*;
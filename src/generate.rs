@@ -0,0 +1,324 @@
+//! Procedural [`shalrath::repr::Map`] generation for benchmarks and tests
+//!
+//! All generators are deterministic, so the same arguments always produce the same map.
+use shalrath::repr::{
+    Brush, BrushPlane, Brushes, Entity, Extension, Map, Point, Properties, Property,
+    TextureOffset, TrianglePlane,
+};
+
+use crate::{Plane3d, Vector3};
+
+#[cfg(test)]
+use crate::{
+    brush::{brush_hulls, BrushHulls},
+    face::{
+        face_centers, face_indices, face_planes, face_vertices, FaceCenters, FaceIndices,
+        FacePlanes, FaceVertexPlanes, FaceVertices, FaceWinding,
+    },
+    GeoMap,
+};
+
+/// Texture applied to generated faces
+pub const TEXTURE: &str = "base";
+
+/// Build a brush from outward-facing planes given as `(normal, distance)`
+pub fn brush_from_planes<I>(planes: I) -> Brush
+where
+    I: IntoIterator<Item = (Vector3, f32)>,
+{
+    Brush(
+        planes
+            .into_iter()
+            .map(|(n, d)| brush_plane(&Plane3d { n: n.normalize(), d }))
+            .collect(),
+    )
+}
+
+/// Axis-aligned box spanning `min` to `max`
+///
+/// Faces are ordered +X, -X, +Y, -Y, +Z, -Z.
+pub fn box_brush(min: Vector3, max: Vector3) -> Brush {
+    brush_from_planes([
+        (Vector3::x(), max.x),
+        (-Vector3::x(), -min.x),
+        (Vector3::y(), max.y),
+        (-Vector3::y(), -min.y),
+        (Vector3::z(), max.z),
+        (-Vector3::z(), -min.z),
+    ])
+}
+
+/// 32 unit cube with its minimum corner at `x` along the X axis
+pub fn cube(x: f32) -> Brush {
+    box_brush(
+        nalgebra::vector![x, 0.0, 0.0],
+        nalgebra::vector![x + 32.0, 32.0, 32.0],
+    )
+}
+
+/// Upright prism with `sides` faces around its circumference
+///
+/// `base` is the center of the bottom face.
+pub fn cylinder_brush(base: Vector3, radius: f32, height: f32, sides: usize) -> Brush {
+    let sides = sides.max(3);
+
+    let walls = (0..sides).map(|i| {
+        let theta = i as f32 / sides as f32 * std::f32::consts::TAU;
+        let n = nalgebra::vector![theta.cos(), theta.sin(), 0.0];
+        (n, n.dot(&base) + radius)
+    });
+
+    brush_from_planes(
+        walls.chain([
+            (Vector3::z(), base.z + height),
+            (-Vector3::z(), -base.z),
+        ]),
+    )
+}
+
+/// Entity with the given properties and brushes
+pub fn entity(properties: &[(&str, &str)], brushes: Vec<Brush>) -> Entity {
+    Entity {
+        properties: Properties(
+            properties
+                .iter()
+                .map(|(key, value)| Property {
+                    key: key.to_string(),
+                    value: value.to_string(),
+                })
+                .collect(),
+        ),
+        brushes: Brushes(brushes),
+    }
+}
+
+/// Map containing a single worldspawn entity
+pub fn worldspawn(brushes: Vec<Brush>) -> Map {
+    Map(vec![entity(&[("classname", "worldspawn")], brushes)])
+}
+
+/// `count` boxes of `size` units per axis, separated by `gap` units
+///
+/// A `gap` of zero produces touching boxes with coincident faces.
+pub fn box_grid(count: [usize; 3], size: f32, gap: f32) -> Map {
+    let stride = size + gap;
+
+    let mut brushes = vec![];
+    for x in 0..count[0] {
+        for y in 0..count[1] {
+            for z in 0..count[2] {
+                let min = nalgebra::vector![x as f32, y as f32, z as f32] * stride;
+                brushes.push(box_brush(min, min.add_scalar(size)));
+            }
+        }
+    }
+
+    worldspawn(brushes)
+}
+
+/// Row of `count` cylinders with `sides` faces each
+pub fn cylinders(count: usize, sides: usize) -> Map {
+    let radius = 32.0;
+
+    worldspawn(
+        (0..count)
+            .map(|i| {
+                let base = nalgebra::vector![i as f32 * radius * 3.0, 0.0, 0.0];
+                cylinder_brush(base, radius, 128.0, sides)
+            })
+            .collect(),
+    )
+}
+
+/// Flight of `steps` stairs, each resting on top of the last
+pub fn staircase(steps: usize) -> Map {
+    let (width, depth, height) = (128.0, 16.0, 8.0);
+
+    worldspawn(
+        (0..steps)
+            .map(|i| {
+                let min = nalgebra::vector![0.0, i as f32 * depth, i as f32 * height];
+                box_brush(min, min + nalgebra::vector![width, depth, height])
+            })
+            .collect(),
+    )
+}
+
+/// `count` boxes of varying size scattered so that most intersect their neighbours
+pub fn overlapping_boxes(count: usize, seed: u64) -> Map {
+    let mut rng = Lcg(seed);
+    let extent = (count as f32).cbrt() * 48.0;
+
+    worldspawn(
+        (0..count)
+            .map(|_| {
+                let min = nalgebra::vector![
+                    (rng.next_f32() * extent).round(),
+                    (rng.next_f32() * extent).round(),
+                    (rng.next_f32() * extent).round()
+                ];
+                let size = nalgebra::vector![
+                    (16.0 + rng.next_f32() * 64.0).round(),
+                    (16.0 + rng.next_f32() * 64.0).round(),
+                    (16.0 + rng.next_f32() * 64.0).round()
+                ];
+                box_brush(min, min + size)
+            })
+            .collect(),
+    )
+}
+
+/// Grid of `rooms` by `rooms` hollow rooms sharing walls, with a pillar and a
+/// brush entity in each
+///
+/// Approximates the brush count and connectivity of a full game level.
+pub fn large_world(rooms: usize) -> Map {
+    let (room, wall, height) = (256.0, 16.0, 192.0);
+
+    let mut world = vec![];
+    let mut entities = vec![];
+
+    for x in 0..rooms {
+        for y in 0..rooms {
+            let min = nalgebra::vector![x as f32 * room, y as f32 * room, 0.0];
+            let max = min + nalgebra::vector![room, room, height];
+
+            // Floor and ceiling
+            world.push(box_brush(min, nalgebra::vector![max.x, max.y, min.z + wall]));
+            world.push(box_brush(nalgebra::vector![min.x, min.y, max.z - wall], max));
+
+            // Walls on the low sides only, so neighbouring rooms share them
+            world.push(box_brush(
+                nalgebra::vector![min.x, min.y, min.z + wall],
+                nalgebra::vector![min.x + wall, max.y, max.z - wall],
+            ));
+            world.push(box_brush(
+                nalgebra::vector![min.x + wall, min.y, min.z + wall],
+                nalgebra::vector![max.x, min.y + wall, max.z - wall],
+            ));
+
+            let center = (min + max) * 0.5;
+            world.push(cylinder_brush(
+                nalgebra::vector![center.x, center.y, min.z + wall],
+                24.0,
+                height - wall * 2.0,
+                12,
+            ));
+
+            entities.push(entity(
+                &[("classname", "func_door")],
+                vec![box_brush(
+                    nalgebra::vector![min.x, center.y - 32.0, min.z + wall],
+                    nalgebra::vector![min.x + wall, center.y + 32.0, min.z + wall + 96.0],
+                )],
+            ));
+        }
+    }
+
+    let mut map = vec![entity(&[("classname", "worldspawn")], world)];
+    map.extend(entities);
+    Map(map)
+}
+
+fn brush_plane(plane: &Plane3d) -> BrushPlane {
+    // Points are ordered so that `Plane3d::from(&TrianglePlane)` recovers the outward normal
+    let (u, v) = plane.axes();
+    let origin = plane.n * plane.d;
+
+    BrushPlane {
+        plane: TrianglePlane {
+            v0: point(origin),
+            v1: point(origin + v * 64.0),
+            v2: point(origin + u * 64.0),
+        },
+        texture: TEXTURE.to_string(),
+        texture_offset: TextureOffset::Standard { u: 0.0, v: 0.0 },
+        angle: 0.0,
+        scale_x: 1.0,
+        scale_y: 1.0,
+        extension: Extension::Standard,
+    }
+}
+
+fn point(v: Vector3) -> Point {
+    Point {
+        x: v.x,
+        y: v.y,
+        z: v.z,
+    }
+}
+
+/// Minimal linear congruential generator, avoiding a dependency on `rand`
+struct Lcg(u64);
+
+impl Lcg {
+    /// Next value in `[0, 1)`
+    fn next_f32(&mut self) -> f32 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (self.0 >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+/// Output of the geometry stages that most later stages build on
+#[cfg(test)]
+pub(crate) struct Pipeline {
+    pub geo_map: GeoMap,
+    pub planes: FacePlanes,
+    pub hulls: BrushHulls,
+    pub vertices: FaceVertices,
+    pub vertex_planes: FaceVertexPlanes,
+    pub centers: FaceCenters,
+    pub indices: FaceIndices,
+}
+
+/// Run `map` through plane, hull, vertex, center and clockwise index generation
+#[cfg(test)]
+pub(crate) fn pipeline(map: Map) -> Pipeline {
+    let geo_map = GeoMap::new(map);
+    let planes = face_planes(&geo_map.face_planes);
+    let hulls = brush_hulls(&geo_map.brush_faces, &planes);
+    let (vertices, vertex_planes) = face_vertices(&geo_map.brush_faces, &planes, &hulls, &());
+    let centers = face_centers(&vertices);
+    let indices = face_indices(&planes, &vertices, &centers, FaceWinding::Clockwise);
+
+    Pipeline {
+        geo_map,
+        planes,
+        hulls,
+        vertices,
+        vertex_planes,
+        centers,
+        indices,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_brushes_are_closed() {
+        let Pipeline {
+            geo_map, vertices, ..
+        } = pipeline(Map(vec![entity(
+            &[("classname", "worldspawn")],
+            vec![
+                box_brush(nalgebra::vector![0.0, 0.0, 0.0], nalgebra::vector![32.0, 64.0, 16.0]),
+                cylinder_brush(nalgebra::vector![128.0, 0.0, 0.0], 32.0, 64.0, 8),
+            ],
+        )]));
+
+        let counts = geo_map
+            .faces
+            .iter()
+            .map(|face_id| vertices[face_id].len())
+            .collect::<Vec<_>>();
+
+        assert_eq!(counts[..6], [4; 6]);
+        assert_eq!(counts[6..14], [4; 8]);
+        assert_eq!(counts[14..], [8, 8]);
+    }
+}
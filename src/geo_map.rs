@@ -44,6 +44,11 @@ pub type Textures = Usage<TexturesTag, BTreeMap<TextureId, String>>;
 
 /// Struct-of-arrays representation of a [`shalrath::repr::Map`]
 #[derive(Debug, Default, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "serde_repr::GeoMapRepr", from = "serde_repr::GeoMapRepr")
+)]
 pub struct GeoMap {
     pub entities: Entities,
     pub brushes: Brushes,
@@ -150,10 +155,7 @@ impl GeoMap {
             .copied()
             .collect();
 
-        let textures = textures
-            .into_iter()
-            .map(|(k, v)| (v, k))
-            .collect();
+        let textures = textures.into_iter().map(|(k, v)| (v, k)).collect();
 
         GeoMap {
             entities,
@@ -179,3 +181,228 @@ impl From<shalrath::repr::Map> for GeoMap {
         GeoMap::new(map)
     }
 }
+
+/// Serializable mirror of [`GeoMap`], standing in for the `shalrath` types it holds
+#[cfg(feature = "serde")]
+mod serde_repr {
+    use std::collections::BTreeMap;
+
+    use serde::{Deserialize, Serialize};
+    use shalrath::repr::{
+        Extension, Point, Properties, Property, TextureOffset, TexturePlane, TrianglePlane,
+    };
+
+    use super::GeoMap;
+    use crate::{brush::BrushId, entity::EntityId, face::FaceId, texture::TextureId, Vector2};
+
+    #[derive(Serialize, Deserialize)]
+    enum TextureOffsetRepr {
+        Standard { u: f32, v: f32 },
+        Valve { u: [f32; 4], v: [f32; 4] },
+    }
+
+    #[derive(Serialize, Deserialize)]
+    enum ExtensionRepr {
+        Standard,
+        Quake2 {
+            content_flags: u32,
+            surface_flags: u32,
+            value: f32,
+        },
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct GeoMapRepr {
+        entities: Vec<EntityId>,
+        brushes: Vec<BrushId>,
+        faces: Vec<FaceId>,
+        textures: BTreeMap<TextureId, String>,
+        entity_properties: BTreeMap<EntityId, Vec<(String, String)>>,
+        entity_brushes: BTreeMap<EntityId, Vec<BrushId>>,
+        point_entities: Vec<EntityId>,
+        brush_faces: BTreeMap<BrushId, Vec<FaceId>>,
+        face_planes: BTreeMap<FaceId, [[f32; 3]; 3]>,
+        face_textures: BTreeMap<FaceId, TextureId>,
+        face_offsets: BTreeMap<FaceId, TextureOffsetRepr>,
+        face_angles: BTreeMap<FaceId, f32>,
+        face_scales: BTreeMap<FaceId, Vector2>,
+        face_extensions: BTreeMap<FaceId, ExtensionRepr>,
+    }
+
+    fn point_repr(Point { x, y, z }: Point) -> [f32; 3] {
+        [x, y, z]
+    }
+
+    fn point([x, y, z]: [f32; 3]) -> Point {
+        Point { x, y, z }
+    }
+
+    fn texture_plane_repr(TexturePlane { x, y, z, d }: TexturePlane) -> [f32; 4] {
+        [x, y, z, d]
+    }
+
+    fn texture_plane([x, y, z, d]: [f32; 4]) -> TexturePlane {
+        TexturePlane { x, y, z, d }
+    }
+
+    impl From<GeoMap> for GeoMapRepr {
+        fn from(geo_map: GeoMap) -> Self {
+            GeoMapRepr {
+                entities: geo_map.entities.to_vec(),
+                brushes: geo_map.brushes.to_vec(),
+                faces: geo_map.faces.to_vec(),
+                textures: (*geo_map.textures).clone(),
+                entity_properties: geo_map
+                    .entity_properties
+                    .iter()
+                    .map(|(entity_id, properties)| {
+                        (
+                            *entity_id,
+                            properties
+                                .0
+                                .iter()
+                                .map(|Property { key, value }| (key.clone(), value.clone()))
+                                .collect(),
+                        )
+                    })
+                    .collect(),
+                entity_brushes: (*geo_map.entity_brushes).clone(),
+                point_entities: geo_map.point_entities.to_vec(),
+                brush_faces: (*geo_map.brush_faces).clone(),
+                face_planes: geo_map
+                    .face_planes
+                    .iter()
+                    .map(|(face_id, plane)| {
+                        (
+                            *face_id,
+                            [
+                                point_repr(plane.v0),
+                                point_repr(plane.v1),
+                                point_repr(plane.v2),
+                            ],
+                        )
+                    })
+                    .collect(),
+                face_textures: (*geo_map.face_textures).clone(),
+                face_offsets: geo_map
+                    .face_offsets
+                    .iter()
+                    .map(|(face_id, offset)| {
+                        let offset = match *offset {
+                            TextureOffset::Standard { u, v } => {
+                                TextureOffsetRepr::Standard { u, v }
+                            }
+                            TextureOffset::Valve { u, v } => TextureOffsetRepr::Valve {
+                                u: texture_plane_repr(u),
+                                v: texture_plane_repr(v),
+                            },
+                        };
+                        (*face_id, offset)
+                    })
+                    .collect(),
+                face_angles: (*geo_map.face_angles).clone(),
+                face_scales: (*geo_map.face_scales).clone(),
+                face_extensions: geo_map
+                    .face_extensions
+                    .iter()
+                    .map(|(face_id, extension)| {
+                        let extension = match *extension {
+                            Extension::Standard => ExtensionRepr::Standard,
+                            Extension::Quake2 {
+                                content_flags,
+                                surface_flags,
+                                value,
+                            } => ExtensionRepr::Quake2 {
+                                content_flags,
+                                surface_flags,
+                                value,
+                            },
+                        };
+                        (*face_id, extension)
+                    })
+                    .collect(),
+            }
+        }
+    }
+
+    impl From<GeoMapRepr> for GeoMap {
+        fn from(repr: GeoMapRepr) -> Self {
+            GeoMap {
+                entities: repr.entities.into_iter().collect(),
+                brushes: repr.brushes.into_iter().collect(),
+                faces: repr.faces.into_iter().collect(),
+                textures: repr.textures.into_iter().collect(),
+                entity_properties: repr
+                    .entity_properties
+                    .into_iter()
+                    .map(|(entity_id, properties)| {
+                        (
+                            entity_id,
+                            Properties(
+                                properties
+                                    .into_iter()
+                                    .map(|(key, value)| Property { key, value })
+                                    .collect(),
+                            ),
+                        )
+                    })
+                    .collect(),
+                entity_brushes: repr.entity_brushes.into_iter().collect(),
+                point_entities: repr.point_entities.into_iter().collect(),
+                brush_faces: repr.brush_faces.into_iter().collect(),
+                face_planes: repr
+                    .face_planes
+                    .into_iter()
+                    .map(|(face_id, [v0, v1, v2])| {
+                        (
+                            face_id,
+                            TrianglePlane {
+                                v0: point(v0),
+                                v1: point(v1),
+                                v2: point(v2),
+                            },
+                        )
+                    })
+                    .collect(),
+                face_textures: repr.face_textures.into_iter().collect(),
+                face_offsets: repr
+                    .face_offsets
+                    .into_iter()
+                    .map(|(face_id, offset)| {
+                        let offset = match offset {
+                            TextureOffsetRepr::Standard { u, v } => {
+                                TextureOffset::Standard { u, v }
+                            }
+                            TextureOffsetRepr::Valve { u, v } => TextureOffset::Valve {
+                                u: texture_plane(u),
+                                v: texture_plane(v),
+                            },
+                        };
+                        (face_id, offset)
+                    })
+                    .collect(),
+                face_angles: repr.face_angles.into_iter().collect(),
+                face_scales: repr.face_scales.into_iter().collect(),
+                face_extensions: repr
+                    .face_extensions
+                    .into_iter()
+                    .map(|(face_id, extension)| {
+                        let extension = match extension {
+                            ExtensionRepr::Standard => Extension::Standard,
+                            ExtensionRepr::Quake2 {
+                                content_flags,
+                                surface_flags,
+                                value,
+                            } => Extension::Quake2 {
+                                content_flags,
+                                surface_flags,
+                                value,
+                            },
+                        };
+                        (face_id, extension)
+                    })
+                    .collect(),
+            }
+        }
+    }
+}
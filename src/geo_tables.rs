@@ -0,0 +1,884 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+use crate::{
+    brush::{brush_bounds, brush_hulls, BrushBounds, BrushHulls, BrushId},
+    diff::{map_diff, MapDiff},
+    face::{
+        self, face_bases, face_centers, face_duplicates, face_indices, face_planes,
+        face_triangle_indices_with, face_vertices, faces_duplicate, interior_faces, normals_flat,
+        normals_phong_averaged, normals_phong_threshold, FaceBases, FaceCenters, FaceDuplicates,
+        FaceId, FaceIndices, FaceLines, FaceNormals, FacePlanes, FaceTriangleIndices, FaceUvs,
+        FaceVertexPlanes, FaceVertices, FaceWinding, InteriorFaces, NormalMode, Triangulation,
+    },
+    line::{
+        line_face_connections, line_faces, lines, lines_connected, manifold_lines,
+        LineFaceConnections, LineFaces, LineId, Lines, ManifoldLines, NonManifoldLines,
+    },
+    texture::TextureSizes,
+    Diagnostics, Faces, GeoMap, Vector3, EPSILON,
+};
+
+/// Derived geometry tables for a [`GeoMap`], supporting incremental updates
+///
+/// Per-face tables are recomputed only for the faces of changed brushes,
+/// while cross-brush tables such as [`LineFaceConnections`] and [`FaceDuplicates`]
+/// are recomputed only for brushes whose bounds touch a changed brush before or after the change.
+/// [`InteriorFaces`] is recomputed over every face connected to those brushes.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GeoTables {
+    pub winding: FaceWinding,
+    pub triangulation: Triangulation,
+    pub normal_mode: NormalMode,
+
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_usage"))]
+    pub face_planes: FacePlanes,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_usage"))]
+    pub brush_hulls: BrushHulls,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_usage"))]
+    pub face_vertices: FaceVertices,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_usage"))]
+    pub face_vertex_planes: FaceVertexPlanes,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_usage"))]
+    pub face_centers: FaceCenters,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_usage"))]
+    pub face_indices: FaceIndices,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_usage"))]
+    pub face_triangle_indices: FaceTriangleIndices,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_usage"))]
+    pub face_normals: FaceNormals,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_usage"))]
+    pub face_uvs: FaceUvs,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_usage"))]
+    pub face_bases: FaceBases,
+
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_usage"))]
+    pub lines: Lines,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_usage"))]
+    pub face_lines: FaceLines,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_usage"))]
+    pub line_faces: LineFaces,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_usage"))]
+    pub line_face_connections: LineFaceConnections,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_usage"))]
+    pub manifold_lines: ManifoldLines,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_usage"))]
+    pub non_manifold_lines: NonManifoldLines,
+
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_usage"))]
+    pub face_duplicates: FaceDuplicates,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_usage"))]
+    pub interior_faces: InteriorFaces,
+
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_usage"))]
+    brush_bounds: BrushBounds,
+}
+
+/// Axis-aligned bounding box as `(min, max)`
+type Bounds = (Vector3, Vector3);
+
+impl GeoTables {
+    /// Compute all tables from scratch
+    ///
+    /// `winding`, `triangulation` and `normal_mode` are stored and reused by incremental updates.
+    pub fn new(
+        geo_map: &GeoMap,
+        texture_sizes: &TextureSizes,
+        winding: FaceWinding,
+        triangulation: Triangulation,
+        normal_mode: NormalMode,
+        diagnostics: &dyn Diagnostics,
+    ) -> Self {
+        let face_planes = face_planes(&geo_map.face_planes);
+        let brush_hulls = brush_hulls(&geo_map.brush_faces, &face_planes);
+        let (face_vertices, face_vertex_planes) = face_vertices(
+            &geo_map.brush_faces,
+            &face_planes,
+            &brush_hulls,
+            diagnostics,
+        );
+        let face_centers = face_centers(&face_vertices);
+        let face_indices = face_indices(&face_planes, &face_vertices, &face_centers, winding);
+        let face_triangle_indices =
+            face_triangle_indices_with(&face_indices, &face_vertices, &face_planes, triangulation);
+        let face_normals = normals(
+            normal_mode,
+            &face_vertices,
+            &face_vertex_planes,
+            &face_planes,
+        );
+        let face_uvs = face::new(
+            &geo_map.faces,
+            &geo_map.textures,
+            &geo_map.face_textures,
+            &face_vertices,
+            &face_planes,
+            &geo_map.face_offsets,
+            &geo_map.face_angles,
+            &geo_map.face_scales,
+            texture_sizes,
+            diagnostics,
+        );
+        let face_bases = face_bases(
+            &geo_map.faces,
+            &face_planes,
+            &geo_map.face_offsets,
+            &geo_map.face_angles,
+            &geo_map.face_scales,
+        );
+
+        let (lines, face_lines) = lines(&face_indices);
+        let line_faces = line_faces(&face_lines);
+        let line_face_connections = line_face_connections(&lines, &line_faces, &face_vertices);
+        let (manifold_lines, non_manifold_lines) = manifold_lines(&line_face_connections);
+
+        let face_duplicates = face_duplicates(&geo_map.faces, &face_planes, &face_vertices);
+        let interior_faces = interior_faces(
+            &geo_map.faces,
+            &face_lines,
+            &face_normals,
+            &face_centers,
+            &non_manifold_lines,
+            &line_face_connections,
+        );
+
+        let brush_bounds = brush_bounds(&geo_map.brush_faces, &face_vertices);
+
+        GeoTables {
+            winding,
+            triangulation,
+            normal_mode,
+            face_planes,
+            brush_hulls,
+            face_vertices,
+            face_vertex_planes,
+            face_centers,
+            face_indices,
+            face_triangle_indices,
+            face_normals,
+            face_uvs,
+            face_bases,
+            lines,
+            face_lines,
+            line_faces,
+            line_face_connections,
+            manifold_lines,
+            non_manifold_lines,
+            face_duplicates,
+            interior_faces,
+            brush_bounds,
+        }
+    }
+
+    /// Bring the tables up to date with `new_map`, which was previously described by `old_map`
+    ///
+    /// If entities, brushes or faces were added or removed, [`GeoMap`] IDs will have shifted,
+    /// so unchanged brushes are matched by geometry with [`map_diff`] and their rows moved to their new IDs.
+    /// Every other brush is recomputed.
+    pub fn update_map(
+        &mut self,
+        old_map: &GeoMap,
+        new_map: &GeoMap,
+        texture_sizes: &TextureSizes,
+        diagnostics: &dyn Diagnostics,
+    ) {
+        match changed_brushes(old_map, new_map) {
+            Some(changed) => self.update(new_map, texture_sizes, &changed, diagnostics),
+            None => {
+                let diff = map_diff(old_map, new_map);
+                let (changed, dropped_bounds) = self.remap(old_map, new_map, &diff);
+                self.update_brushes(
+                    new_map,
+                    texture_sizes,
+                    &changed,
+                    &dropped_bounds,
+                    diagnostics,
+                );
+            }
+        }
+    }
+
+    /// Recompute the tables affected by changes to the given brushes
+    ///
+    /// `geo_map` must contain the changed brushes under their existing [`BrushId`]s and [`FaceId`]s.
+    pub fn update(
+        &mut self,
+        geo_map: &GeoMap,
+        texture_sizes: &TextureSizes,
+        changed: &BTreeSet<BrushId>,
+        diagnostics: &dyn Diagnostics,
+    ) {
+        self.update_brushes(geo_map, texture_sizes, changed, &[], diagnostics);
+    }
+
+    /// Move the rows of unchanged brushes matched by `diff` to their IDs in `new_map`,
+    /// and drop the rows of every other brush in `old_map`
+    ///
+    /// Returns the brushes in `new_map` that need computing, and the bounds of the dropped brushes.
+    fn remap(
+        &mut self,
+        old_map: &GeoMap,
+        new_map: &GeoMap,
+        diff: &MapDiff,
+    ) -> (BTreeSet<BrushId>, Vec<Bounds>) {
+        let brush_ids = diff
+            .brush_matches
+            .iter()
+            .map(|(old_id, new_id)| (*old_id, *new_id))
+            .filter(|(old_id, new_id)| {
+                let old_faces = &old_map.brush_faces[old_id];
+                let new_faces = &new_map.brush_faces[new_id];
+                old_faces.len() == new_faces.len()
+                    && !old_faces.iter().zip(new_faces).any(|(old_face, new_face)| {
+                        face_changed(old_map, new_map, old_face, new_face)
+                    })
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        let face_ids = brush_ids
+            .iter()
+            .flat_map(|(old_id, new_id)| {
+                old_map.brush_faces[old_id]
+                    .iter()
+                    .copied()
+                    .zip(new_map.brush_faces[new_id].iter().copied())
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        let matched = brush_ids.values().copied().collect::<BTreeSet<_>>();
+        let changed = new_map
+            .brush_faces
+            .keys()
+            .filter(|brush_id| !matched.contains(*brush_id))
+            .copied()
+            .collect();
+
+        let dropped_bounds = old_map
+            .brush_faces
+            .keys()
+            .filter(|brush_id| !brush_ids.contains_key(*brush_id))
+            .filter_map(|brush_id| self.brush_bounds.get(brush_id).copied())
+            .collect();
+
+        // Lines keep their IDs, so only the lines of dropped faces are removed
+        let dropped_lines = self
+            .face_lines
+            .iter()
+            .filter(|(face_id, _)| !face_ids.contains_key(*face_id))
+            .flat_map(|(_, line_ids)| line_ids.iter().copied())
+            .collect::<Vec<_>>();
+
+        for line_id in dropped_lines {
+            self.lines.remove(&line_id);
+            self.line_faces.remove(&line_id);
+            self.line_face_connections.remove(&line_id);
+            self.manifold_lines.remove(&line_id);
+            self.non_manifold_lines.remove(&line_id);
+        }
+
+        rekey(&mut self.brush_hulls, &brush_ids);
+        rekey(&mut self.brush_bounds, &brush_ids);
+
+        rekey(&mut self.face_planes, &face_ids);
+        rekey(&mut self.face_vertices, &face_ids);
+        rekey(&mut self.face_vertex_planes, &face_ids);
+        rekey(&mut self.face_centers, &face_ids);
+        rekey(&mut self.face_indices, &face_ids);
+        rekey(&mut self.face_triangle_indices, &face_ids);
+        rekey(&mut self.face_normals, &face_ids);
+        rekey(&mut self.face_uvs, &face_ids);
+        rekey(&mut self.face_bases, &face_ids);
+        rekey(&mut self.face_lines, &face_ids);
+
+        // Vertex planes always belong to the same brush as their face
+        for planes in self.face_vertex_planes.values_mut() {
+            for (p0, p1, p2) in planes {
+                *p0 = face_ids[&*p0];
+                *p1 = face_ids[&*p1];
+                *p2 = face_ids[&*p2];
+            }
+        }
+
+        for face_id in self.line_faces.values_mut() {
+            *face_id = face_ids[&*face_id];
+        }
+
+        // Connections to dropped faces are recomputed with their neighbours
+        for connected in self.line_face_connections.values_mut() {
+            *connected = connected
+                .iter()
+                .filter_map(|face_id| face_ids.get(face_id).copied())
+                .collect();
+        }
+
+        *self.face_duplicates = self
+            .face_duplicates
+            .iter()
+            .filter_map(|(lhs, rhs)| Some((*face_ids.get(lhs)?, *face_ids.get(rhs)?)))
+            .collect();
+
+        *self.interior_faces = self
+            .interior_faces
+            .iter()
+            .filter_map(|face_id| face_ids.get(face_id).copied())
+            .collect();
+
+        (changed, dropped_bounds)
+    }
+
+    /// Recompute the tables affected by changes to the given brushes,
+    /// or by removing brushes that occupied `dropped_bounds`
+    fn update_brushes(
+        &mut self,
+        geo_map: &GeoMap,
+        texture_sizes: &TextureSizes,
+        changed: &BTreeSet<BrushId>,
+        dropped_bounds: &[Bounds],
+        diagnostics: &dyn Diagnostics,
+    ) {
+        if changed.is_empty() && dropped_bounds.is_empty() {
+            return;
+        }
+
+        let changed_brush_faces = changed
+            .iter()
+            .map(|brush_id| (*brush_id, geo_map.brush_faces[brush_id].clone()))
+            .collect::<BTreeMap<_, _>>();
+
+        let changed_faces = changed_brush_faces
+            .values()
+            .flatten()
+            .copied()
+            .collect::<Vec<_>>();
+
+        let old_bounds = changed
+            .iter()
+            .filter_map(|brush_id| self.brush_bounds.get(brush_id).copied())
+            .chain(dropped_bounds.iter().copied())
+            .collect::<Vec<_>>();
+
+        // Per-face tables
+        let triangle_planes = changed_faces
+            .iter()
+            .map(|face_id| (*face_id, geo_map.face_planes[face_id]))
+            .collect::<BTreeMap<_, _>>();
+        self.face_planes.append(&mut face_planes(&triangle_planes));
+
+        self.brush_hulls
+            .append(&mut brush_hulls(&changed_brush_faces, &self.face_planes));
+
+        let (mut changed_vertices, mut changed_vertex_planes) = face_vertices(
+            &changed_brush_faces,
+            &self.face_planes,
+            &self.brush_hulls,
+            diagnostics,
+        );
+        let mut changed_centers = face_centers(&changed_vertices);
+        let mut changed_indices = face_indices(
+            &self.face_planes,
+            &changed_vertices,
+            &changed_centers,
+            self.winding,
+        );
+
+        self.face_triangle_indices
+            .append(&mut face_triangle_indices_with(
+                &changed_indices,
+                &changed_vertices,
+                &self.face_planes,
+                self.triangulation,
+            ));
+        self.face_normals.append(&mut normals(
+            self.normal_mode,
+            &changed_vertices,
+            &changed_vertex_planes,
+            &self.face_planes,
+        ));
+        self.face_uvs.append(&mut face::new(
+            &changed_faces,
+            &geo_map.textures,
+            &geo_map.face_textures,
+            &changed_vertices,
+            &self.face_planes,
+            &geo_map.face_offsets,
+            &geo_map.face_angles,
+            &geo_map.face_scales,
+            texture_sizes,
+            diagnostics,
+        ));
+        self.face_bases.append(&mut face_bases(
+            &changed_faces,
+            &self.face_planes,
+            &geo_map.face_offsets,
+            &geo_map.face_angles,
+            &geo_map.face_scales,
+        ));
+
+        // Replace the lines of changed faces, allocating fresh IDs past the current maximum
+        let line_head = self
+            .lines
+            .keys()
+            .next_back()
+            .map_or(0, |line_id| line_id.0 + 1);
+
+        for face_id in &changed_faces {
+            for line_id in self.face_lines.remove(face_id).unwrap_or_default() {
+                self.lines.remove(&line_id);
+                self.line_faces.remove(&line_id);
+                self.line_face_connections.remove(&line_id);
+                self.manifold_lines.remove(&line_id);
+                self.non_manifold_lines.remove(&line_id);
+            }
+        }
+
+        let (changed_lines, changed_face_lines) = lines(&changed_indices);
+        self.lines.extend(
+            changed_lines
+                .iter()
+                .map(|(line_id, line)| (LineId(line_id.0 + line_head), *line)),
+        );
+        for (face_id, line_ids) in changed_face_lines.iter() {
+            for line_id in line_ids {
+                self.line_faces
+                    .insert(LineId(line_id.0 + line_head), *face_id);
+            }
+            self.face_lines.insert(
+                *face_id,
+                line_ids
+                    .iter()
+                    .map(|line_id| LineId(line_id.0 + line_head))
+                    .collect(),
+            );
+        }
+
+        self.face_vertices.append(&mut changed_vertices);
+        self.face_vertex_planes.append(&mut changed_vertex_planes);
+        self.face_centers.append(&mut changed_centers);
+        self.face_indices.append(&mut changed_indices);
+
+        for brush_id in changed {
+            self.brush_bounds.remove(brush_id);
+        }
+        self.brush_bounds
+            .append(&mut brush_bounds(&changed_brush_faces, &self.face_vertices));
+
+        // Brushes touching a changed brush before or after the change
+        let new_bounds = changed
+            .iter()
+            .filter_map(|brush_id| self.brush_bounds.get(brush_id).copied())
+            .collect::<Vec<_>>();
+
+        let affected_brushes = geo_map
+            .brush_faces
+            .keys()
+            .filter(|brush_id| {
+                changed.contains(*brush_id)
+                    || self.brush_bounds.get(*brush_id).iter().any(|bounds| {
+                        old_bounds
+                            .iter()
+                            .chain(new_bounds.iter())
+                            .any(|other| bounds_overlap(bounds, other))
+                    })
+            })
+            .copied()
+            .collect::<BTreeSet<_>>();
+
+        let affected_faces = affected_brushes
+            .iter()
+            .flat_map(|brush_id| &geo_map.brush_faces[brush_id])
+            .copied()
+            .collect::<BTreeSet<_>>();
+
+        self.update_line_face_connections(geo_map, &affected_brushes);
+        self.update_face_duplicates(geo_map, &affected_brushes, &affected_faces);
+        self.update_interior_faces(&affected_faces);
+    }
+
+    /// Recompute connections for every line belonging to the affected brushes
+    fn update_line_face_connections(
+        &mut self,
+        geo_map: &GeoMap,
+        affected_brushes: &BTreeSet<BrushId>,
+    ) {
+        let lines = &self.lines;
+        let face_lines = &self.face_lines;
+        let face_vertices = &self.face_vertices;
+        let brush_bounds = &self.brush_bounds;
+
+        let connections = affected_brushes
+            .par_iter()
+            .flat_map(|brush_id| {
+                let candidate_lines = match brush_bounds.get(brush_id) {
+                    Some(bounds) => brush_bounds
+                        .iter()
+                        .filter(|(_, other)| bounds_overlap(bounds, other))
+                        .flat_map(|(other_id, _)| &geo_map.brush_faces[other_id])
+                        .flat_map(|face_id| {
+                            face_lines
+                                .get(face_id)
+                                .into_iter()
+                                .flatten()
+                                .map(move |line_id| (*line_id, *face_id))
+                        })
+                        .collect::<Vec<_>>(),
+                    None => vec![],
+                };
+
+                geo_map.brush_faces[brush_id]
+                    .iter()
+                    .flat_map(|face_id| {
+                        face_lines
+                            .get(face_id)
+                            .into_iter()
+                            .flatten()
+                            .map(move |line_id| (*line_id, *face_id))
+                    })
+                    .map(|(lhs_id, lhs_face)| {
+                        let lhs = lines[&lhs_id];
+                        let lhs_v0 = &face_vertices[&lhs_face][lhs.i0];
+                        let lhs_v1 = &face_vertices[&lhs_face][lhs.i1];
+
+                        let mut connected = BTreeSet::default();
+                        connected.insert(lhs_face);
+
+                        for (rhs_id, rhs_face) in &candidate_lines {
+                            if *rhs_id == lhs_id {
+                                continue;
+                            }
+
+                            let rhs = lines[rhs_id];
+                            let rhs_v0 = &face_vertices[rhs_face][rhs.i0];
+                            let rhs_v1 = &face_vertices[rhs_face][rhs.i1];
+
+                            if lines_connected(lhs_v0, lhs_v1, rhs_v0, rhs_v1) {
+                                connected.insert(*rhs_face);
+                            }
+                        }
+
+                        (lhs_id, connected)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        for (line_id, connected) in connections {
+            // Matches the classification in `manifold_lines`
+            if connected.len() > 2 {
+                self.manifold_lines.remove(&line_id);
+                self.non_manifold_lines.insert(line_id);
+            } else {
+                self.non_manifold_lines.remove(&line_id);
+                self.manifold_lines.insert(line_id);
+            }
+
+            self.line_face_connections.insert(line_id, connected);
+        }
+    }
+
+    /// Recompute duplicate pairs involving the affected faces
+    fn update_face_duplicates(
+        &mut self,
+        geo_map: &GeoMap,
+        affected_brushes: &BTreeSet<BrushId>,
+        affected_faces: &BTreeSet<FaceId>,
+    ) {
+        self.face_duplicates
+            .retain(|(lhs, rhs)| !affected_faces.contains(lhs) && !affected_faces.contains(rhs));
+
+        let face_planes = &self.face_planes;
+        let face_vertices = &self.face_vertices;
+        let brush_bounds = &self.brush_bounds;
+
+        let duplicates = affected_brushes
+            .par_iter()
+            .flat_map(|brush_id| {
+                let bounds = match brush_bounds.get(brush_id) {
+                    Some(bounds) => bounds,
+                    None => return vec![],
+                };
+
+                let candidates = brush_bounds
+                    .iter()
+                    .filter(|(_, other)| bounds_overlap(bounds, other))
+                    .flat_map(|(other_id, _)| &geo_map.brush_faces[other_id])
+                    .collect::<Vec<_>>();
+
+                geo_map.brush_faces[brush_id]
+                    .iter()
+                    .flat_map(|lhs_id| {
+                        candidates
+                            .iter()
+                            .filter(move |rhs_id| **rhs_id != lhs_id)
+                            .filter(move |rhs_id| {
+                                faces_duplicate(
+                                    &face_planes[lhs_id],
+                                    &face_vertices[lhs_id],
+                                    &face_planes[rhs_id],
+                                    &face_vertices[rhs_id],
+                                )
+                            })
+                            .flat_map(move |rhs_id| [(*lhs_id, **rhs_id), (**rhs_id, *lhs_id)])
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        self.face_duplicates.extend(duplicates);
+    }
+
+    /// Re-run interior face traversal over every face connected to the affected faces
+    ///
+    /// Traversal only follows [`LineFaceConnections`],
+    /// so faces outside the connected component keep their previous classification.
+    fn update_interior_faces(&mut self, affected_faces: &BTreeSet<FaceId>) {
+        let mut component = BTreeSet::default();
+        let mut queue = affected_faces
+            .iter()
+            .filter(|face_id| self.face_lines.contains_key(face_id))
+            .copied()
+            .collect::<VecDeque<_>>();
+
+        while let Some(face_id) = queue.pop_front() {
+            if !component.insert(face_id) {
+                continue;
+            }
+
+            for line_id in &self.face_lines[&face_id] {
+                queue.extend(
+                    self.line_face_connections[line_id]
+                        .iter()
+                        .filter(|connected| !component.contains(*connected)),
+                );
+            }
+        }
+
+        self.interior_faces
+            .retain(|face_id| !component.contains(face_id));
+
+        let faces = component.into_iter().collect::<Faces>();
+        self.interior_faces.append(&mut interior_faces(
+            &faces,
+            &self.face_lines,
+            &self.face_normals,
+            &self.face_centers,
+            &self.non_manifold_lines,
+            &self.line_face_connections,
+        ));
+    }
+}
+
+/// Find brushes whose faces differ between two maps with the same entity, brush and face layout
+///
+/// Returns `None` if the layout differs, in which case IDs are not comparable between the two maps.
+pub fn changed_brushes(old_map: &GeoMap, new_map: &GeoMap) -> Option<BTreeSet<BrushId>> {
+    if *old_map.entity_brushes != *new_map.entity_brushes
+        || *old_map.brush_faces != *new_map.brush_faces
+    {
+        return None;
+    }
+
+    Some(
+        new_map
+            .brush_faces
+            .iter()
+            .filter(|(_, face_ids)| {
+                face_ids
+                    .iter()
+                    .any(|face_id| face_changed(old_map, new_map, face_id, face_id))
+            })
+            .map(|(brush_id, _)| *brush_id)
+            .collect(),
+    )
+}
+
+/// Returns true if a face's plane, texture or alignment differs between two maps
+fn face_changed(old_map: &GeoMap, new_map: &GeoMap, old_id: &FaceId, new_id: &FaceId) -> bool {
+    old_map.face_planes[old_id] != new_map.face_planes[new_id]
+        || old_map.textures[&old_map.face_textures[old_id]]
+            != new_map.textures[&new_map.face_textures[new_id]]
+        || old_map.face_offsets[old_id] != new_map.face_offsets[new_id]
+        || old_map.face_angles[old_id] != new_map.face_angles[new_id]
+        || old_map.face_scales[old_id] != new_map.face_scales[new_id]
+        || old_map.face_extensions[old_id] != new_map.face_extensions[new_id]
+}
+
+/// Move rows to their new IDs, dropping rows without one
+fn rekey<K: Ord + Copy, V>(table: &mut BTreeMap<K, V>, ids: &BTreeMap<K, K>) {
+    *table = std::mem::take(table)
+        .into_iter()
+        .filter_map(|(id, row)| Some((*ids.get(&id)?, row)))
+        .collect();
+}
+
+/// Generate normals for the given faces using `mode`
+fn normals(
+    mode: NormalMode,
+    face_vertices: &FaceVertices,
+    face_vertex_planes: &FaceVertexPlanes,
+    face_planes: &FacePlanes,
+) -> FaceNormals {
+    match mode {
+        NormalMode::Flat => normals_flat(face_vertices, face_planes),
+        NormalMode::PhongAveraged => normals_phong_averaged(face_vertex_planes, face_planes),
+        NormalMode::PhongThreshold(threshold) => {
+            normals_phong_threshold(face_vertex_planes, face_planes, threshold)
+        }
+    }
+}
+
+fn bounds_overlap((lhs_min, lhs_max): &Bounds, (rhs_min, rhs_max): &Bounds) -> bool {
+    (0..3).all(|i| lhs_min[i] <= rhs_max[i] + EPSILON && rhs_min[i] <= lhs_max[i] + EPSILON)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::{cube, worldspawn};
+
+    fn geo_map(xs: &[f32]) -> GeoMap {
+        GeoMap::new(worldspawn(xs.iter().map(|x| cube(*x)).collect()))
+    }
+
+    fn tables(geo_map: &GeoMap) -> GeoTables {
+        GeoTables::new(
+            geo_map,
+            &TextureSizes::default(),
+            FaceWinding::Clockwise,
+            Triangulation::EarClip,
+            NormalMode::PhongAveraged,
+            &(),
+        )
+    }
+
+    /// Line tables keyed by face, since incremental updates allocate fresh line IDs
+    fn face_line_tables(
+        tables: &GeoTables,
+    ) -> BTreeMap<FaceId, Vec<(usize, usize, BTreeSet<FaceId>, bool)>> {
+        tables
+            .face_lines
+            .iter()
+            .map(|(face_id, line_ids)| {
+                (
+                    *face_id,
+                    line_ids
+                        .iter()
+                        .map(|line_id| {
+                            assert_eq!(tables.line_faces[line_id], *face_id);
+                            assert_ne!(
+                                tables.manifold_lines.contains(line_id),
+                                tables.non_manifold_lines.contains(line_id)
+                            );
+
+                            let line = tables.lines[line_id];
+                            (
+                                line.i0,
+                                line.i1,
+                                tables.line_face_connections[line_id].clone(),
+                                tables.non_manifold_lines.contains(line_id),
+                            )
+                        })
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+
+    fn assert_tables_eq(lhs: &GeoTables, rhs: &GeoTables) {
+        assert_eq!(*lhs.face_planes, *rhs.face_planes);
+        assert_eq!(*lhs.face_vertices, *rhs.face_vertices);
+        assert_eq!(*lhs.face_vertex_planes, *rhs.face_vertex_planes);
+        assert_eq!(*lhs.face_centers, *rhs.face_centers);
+        assert_eq!(*lhs.face_indices, *rhs.face_indices);
+        assert_eq!(*lhs.face_triangle_indices, *rhs.face_triangle_indices);
+        assert_eq!(*lhs.face_normals, *rhs.face_normals);
+        assert_eq!(*lhs.face_uvs, *rhs.face_uvs);
+        assert_eq!(*lhs.face_bases, *rhs.face_bases);
+        assert_eq!(lhs.lines.len(), rhs.lines.len());
+        assert_eq!(face_line_tables(lhs), face_line_tables(rhs));
+        assert_eq!(*lhs.face_duplicates, *rhs.face_duplicates);
+        assert_eq!(*lhs.interior_faces, *rhs.interior_faces);
+        assert_eq!(*lhs.brush_bounds, *rhs.brush_bounds);
+    }
+
+    fn assert_update_matches_new(old_map: &GeoMap, new_map: &GeoMap) {
+        let mut updated = tables(old_map);
+        updated.update_map(old_map, new_map, &TextureSizes::default(), &());
+
+        assert_tables_eq(&updated, &tables(new_map));
+    }
+
+    #[test]
+    fn test_update_moved_brush() {
+        let old_map = geo_map(&[0.0, 48.0, 96.0]);
+
+        // Move the middle cube out of its gap and into its neighbour
+        let new_map = geo_map(&[0.0, 72.0, 96.0]);
+        assert_eq!(
+            changed_brushes(&old_map, &new_map),
+            Some(std::iter::once(BrushId(1)).collect())
+        );
+        assert_update_matches_new(&old_map, &new_map);
+
+        // And back again
+        assert_update_matches_new(&new_map, &old_map);
+    }
+
+    #[test]
+    fn test_update_overlapping_chain() {
+        // Each cube overlaps the next, so a move can reclassify faces
+        // beyond the brushes whose bounds touch the moved one
+        let old_map = geo_map(&[0.0, 16.0, 32.0, 48.0, 64.0, 80.0]);
+        let new_map = geo_map(&[-64.0, 16.0, 32.0, 48.0, 64.0, 80.0]);
+        assert_update_matches_new(&old_map, &new_map);
+        assert_update_matches_new(&new_map, &old_map);
+    }
+
+    #[test]
+    fn test_update_added_brush() {
+        let old_map = geo_map(&[0.0, 96.0]);
+        let new_map = geo_map(&[0.0, 16.0, 96.0]);
+        assert_eq!(changed_brushes(&old_map, &new_map), None);
+        assert_update_matches_new(&old_map, &new_map);
+    }
+
+    #[test]
+    fn test_update_reordered_brushes() {
+        // Existing brushes shuffle to new IDs behind an added one, so their rows are moved
+        let old_map = geo_map(&[0.0, 16.0, 96.0]);
+        let new_map = geo_map(&[200.0, 96.0, 0.0, 16.0]);
+        assert_eq!(changed_brushes(&old_map, &new_map), None);
+        assert_eq!(map_diff(&old_map, &new_map).brush_matches.len(), 3);
+        assert_update_matches_new(&old_map, &new_map);
+        assert_update_matches_new(&new_map, &old_map);
+    }
+
+    #[test]
+    fn test_update_deleted_brush() {
+        let old_map = geo_map(&[0.0, 16.0, 96.0]);
+        let new_map = geo_map(&[0.0, 96.0]);
+        assert_eq!(changed_brushes(&old_map, &new_map), None);
+        assert_update_matches_new(&old_map, &new_map);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let geo_map = geo_map(&[0.0, 16.0, 96.0]);
+        let tables = tables(&geo_map);
+
+        let mut bytes = vec![];
+        crate::write_cache(&mut bytes, 0, &tables).unwrap();
+        let read: GeoTables = crate::read_cache(bytes.as_slice(), 0).unwrap();
+
+        assert_eq!(read.winding, tables.winding);
+        assert_eq!(read.triangulation, tables.triangulation);
+        assert_eq!(read.normal_mode, tables.normal_mode);
+        assert_eq!(
+            read.lines.keys().collect::<Vec<_>>(),
+            tables.lines.keys().collect::<Vec<_>>()
+        );
+        assert_tables_eq(&read, &tables);
+    }
+}
@@ -0,0 +1,221 @@
+//! Property-based checks of geometric invariants across the pipeline
+//!
+//! Brushes are built from planes tangent to a sphere, so every plane contributes a face,
+//! and plane normals are drawn from the 26 cube-neighbour directions
+//! to keep vertices well separated.
+use proptest::prelude::*;
+use shalrath::repr::{Brush, Map, TextureOffset};
+
+use crate::{
+    face::{self, face_indices, face_texel_gradients, FaceWinding, DEFAULT_TEXTURE_SIZE},
+    generate::{brush_from_planes, pipeline, worldspawn, Pipeline},
+    texture::TextureSizes,
+    Vector3,
+};
+
+/// Tolerance for comparisons involving derived vertex positions
+const TOLERANCE: f32 = 0.01;
+
+fn directions() -> Vec<Vector3> {
+    let mut directions = vec![];
+    for x in -1..=1 {
+        for y in -1..=1 {
+            for z in -1..=1 {
+                if (x, y, z) != (0, 0, 0) {
+                    directions.push(nalgebra::vector![x as f32, y as f32, z as f32].normalize());
+                }
+            }
+        }
+    }
+    directions
+}
+
+fn texture_offset() -> impl Strategy<Value = TextureOffset> {
+    (-64i32..64, -64i32..64).prop_map(|(u, v)| TextureOffset::Standard {
+        u: u as f32,
+        v: v as f32,
+    })
+}
+
+/// Convex brush tangent to a sphere, always bounded by the six axis planes
+fn convex_brush() -> impl Strategy<Value = Brush> {
+    (
+        prop::array::uniform3(-128i32..128),
+        16i32..96,
+        prop::sample::subsequence(directions(), 0..=26),
+        prop::collection::vec((texture_offset(), -180i32..180, 1u32..16, 1u32..16), 32),
+    )
+        .prop_map(|(center, radius, extra, textures)| {
+            let center = nalgebra::vector![center[0] as f32, center[1] as f32, center[2] as f32];
+            let radius = radius as f32;
+
+            let axes = [
+                Vector3::x(),
+                -Vector3::x(),
+                Vector3::y(),
+                -Vector3::y(),
+                Vector3::z(),
+                -Vector3::z(),
+            ];
+
+            let mut normals = axes.to_vec();
+            for n in extra {
+                if !normals.iter().any(|rhs| (n - rhs).magnitude() < TOLERANCE) {
+                    normals.push(n);
+                }
+            }
+
+            let mut brush =
+                brush_from_planes(normals.into_iter().map(|n| (n, n.dot(&center) + radius)));
+
+            for (plane, (offset, angle, scale_x, scale_y)) in brush.0.iter_mut().zip(textures) {
+                plane.texture_offset = offset;
+                plane.angle = angle as f32;
+                plane.scale_x = scale_x as f32 * 0.25;
+                plane.scale_y = scale_y as f32 * 0.25;
+            }
+
+            brush
+        })
+}
+
+fn map() -> impl Strategy<Value = Map> {
+    prop::collection::vec(convex_brush(), 1..4).prop_map(worldspawn)
+}
+
+/// Index of the first vertex within [`TOLERANCE`] of `vertex`, inserting it if none is found
+fn weld(welded: &mut Vec<Vector3>, vertex: &Vector3) -> usize {
+    match welded
+        .iter()
+        .position(|rhs| (vertex - rhs).magnitude() < TOLERANCE)
+    {
+        Some(i) => i,
+        None => {
+            welded.push(*vertex);
+            welded.len() - 1
+        }
+    }
+}
+
+fn polygon_normal(vertices: &[Vector3], indices: &[usize]) -> Vector3 {
+    let mut normal = Vector3::zeros();
+    for i in 0..indices.len() {
+        let a = vertices[indices[i]];
+        let b = vertices[indices[(i + 1) % indices.len()]];
+        normal += a.cross(&b);
+    }
+    normal.normalize()
+}
+
+proptest! {
+    #[test]
+    fn test_face_vertices(map in map()) {
+        let Pipeline { geo_map, planes, hulls, vertices, .. } = pipeline(map);
+
+        for (brush_id, face_ids) in geo_map.brush_faces.iter() {
+            let hull = &hulls[brush_id];
+
+            for face_id in face_ids {
+                let plane = &planes[face_id];
+                let vertices = &vertices[face_id];
+
+                // Vertices where more than three planes meet can appear more than once
+                let mut welded = vec![];
+                for vertex in vertices {
+                    weld(&mut welded, vertex);
+                }
+                prop_assert!(welded.len() >= 3);
+
+                for vertex in vertices {
+                    prop_assert!((plane.normal().dot(vertex) - plane.distance()).abs() < TOLERANCE);
+                    prop_assert!(hull.contains(vertex));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_face_indices(map in map()) {
+        let Pipeline { geo_map, planes, vertices, centers, .. } = pipeline(map);
+
+        for (winding, sign) in [(FaceWinding::Clockwise, 1.0), (FaceWinding::CounterClockwise, -1.0)] {
+            let indices = face_indices(&planes, &vertices, &centers, winding);
+
+            for face_id in geo_map.faces.iter() {
+                let indices = &indices[face_id];
+                prop_assert!(indices.len() >= 3);
+
+                let normal = polygon_normal(&vertices[face_id], indices);
+                prop_assert!((normal - planes[face_id].normal() * sign).magnitude() < TOLERANCE);
+            }
+        }
+    }
+
+    #[test]
+    fn test_euler_characteristic(map in map()) {
+        let Pipeline { geo_map, vertices, indices, .. } = pipeline(map);
+
+        for (brush_id, face_ids) in geo_map.brush_faces.iter() {
+            let mut welded = vec![];
+            let mut edges = std::collections::BTreeSet::new();
+
+            for face_id in face_ids {
+                let face_vertices = &vertices[face_id];
+                let polygon = indices[face_id]
+                    .iter()
+                    .map(|i| weld(&mut welded, &face_vertices[*i]))
+                    .collect::<Vec<_>>();
+
+                for i in 0..polygon.len() {
+                    let (a, b) = (polygon[i], polygon[(i + 1) % polygon.len()]);
+                    edges.insert((a.min(b), a.max(b)));
+                }
+            }
+
+            let euler = welded.len() as isize - edges.len() as isize + face_ids.len() as isize;
+            prop_assert_eq!(euler, 2, "brush {:?}", brush_id);
+        }
+    }
+
+    #[test]
+    fn test_face_uvs(map in map()) {
+        let Pipeline { geo_map, planes, vertices, .. } = pipeline(map);
+
+        let uvs = face::new(
+            &geo_map.faces,
+            &geo_map.textures,
+            &geo_map.face_textures,
+            &vertices,
+            &planes,
+            &geo_map.face_offsets,
+            &geo_map.face_angles,
+            &geo_map.face_scales,
+            &TextureSizes::default(),
+            &(),
+        );
+
+        let gradients = face_texel_gradients(
+            &geo_map.faces,
+            &planes,
+            &geo_map.face_offsets,
+            &geo_map.face_angles,
+            &geo_map.face_scales,
+        );
+
+        let texture_size = nalgebra::vector![DEFAULT_TEXTURE_SIZE.0 as f32, DEFAULT_TEXTURE_SIZE.1 as f32];
+
+        // UVs are affine in position, so differences between vertices follow the texel gradient
+        for face_id in geo_map.faces.iter() {
+            let (vertices, uvs) = (&vertices[face_id], &uvs[face_id]);
+            prop_assert_eq!(uvs.len(), vertices.len());
+
+            let gradient = &gradients[face_id];
+            for (vertex, uv) in vertices.iter().zip(uvs.iter()).skip(1) {
+                let delta = vertex - vertices[0];
+                let expected = nalgebra::vector![delta.dot(&gradient.u), delta.dot(&gradient.v)]
+                    .component_div(&texture_size);
+                prop_assert!((uv - uvs[0] - expected).magnitude() < TOLERANCE);
+            }
+        }
+    }
+}
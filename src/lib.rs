@@ -2,18 +2,36 @@
 //       Use brush hulls to check against each vertex of a face
 
 pub mod brush;
+pub mod diff;
 pub mod entity;
 pub mod face;
+pub mod generate;
 pub mod texture;
 pub mod line;
+pub mod lightmap;
 
+#[cfg(feature = "serde")]
+pub mod serde_usage;
+
+#[cfg(feature = "serde")]
+mod cache;
 mod convex_hull;
+mod diagnostics;
 mod geo_map;
+mod geo_tables;
+#[cfg(test)]
+mod invariants;
 mod plane_3d;
+mod stable_id;
 
+#[cfg(feature = "serde")]
+pub use cache::*;
 pub use convex_hull::*;
+pub use diagnostics::*;
 pub use geo_map::*;
+pub use geo_tables::*;
 pub use plane_3d::*;
+pub use stable_id::*;
 
 pub use shalrath;
 
@@ -25,6 +43,7 @@ const EPSILON: f32 = 0.001;
 
 pub type Vector2 = nalgebra::Vector2<f32>;
 pub type Vector3 = nalgebra::Vector3<f32>;
+pub type Vector4 = nalgebra::Vector4<f32>;
 
 pub fn vector3_from_point(point: Point) -> Vector3 {
     nalgebra::vector![point.x, point.y, point.z]
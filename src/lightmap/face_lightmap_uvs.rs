@@ -0,0 +1,119 @@
+use std::collections::BTreeMap;
+
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use usage::Usage;
+
+use super::{ChartPlacements, FaceCharts, LightmapAtlases, LightmapCharts};
+use crate::{
+    face::{FaceId, FaceVertices},
+    Vector2,
+};
+
+pub enum FaceLightmapUvsTag {}
+
+/// Per-vertex lightmap UVs in the space of the atlas given by each face's chart placement
+pub type FaceLightmapUvs = Usage<FaceLightmapUvsTag, BTreeMap<FaceId, Vec<Vector2>>>;
+
+pub fn face_lightmap_uvs(
+    face_vertices: &FaceVertices,
+    face_charts: &FaceCharts,
+    charts: &LightmapCharts,
+    placements: &ChartPlacements,
+    atlases: &LightmapAtlases,
+) -> FaceLightmapUvs {
+    face_vertices
+        .par_iter()
+        .flat_map(|(face_id, vertices)| {
+            let chart_id = face_charts.get(face_id)?;
+            let chart = &charts[chart_id];
+            let placement = &placements[chart_id];
+            let (atlas_width, atlas_height) = atlases[placement.atlas];
+
+            // Luxel centers sit half a texel in from each luxel's corner
+            let offset = nalgebra::vector![placement.x as f32 + 0.5, placement.y as f32 + 0.5];
+            let scale = nalgebra::vector![1.0 / atlas_width as f32, 1.0 / atlas_height as f32];
+
+            Some((
+                *face_id,
+                vertices
+                    .iter()
+                    .map(|vertex| {
+                        (chart.luxel_coords(vertex) - chart.min + offset).component_mul(&scale)
+                    })
+                    .collect(),
+            ))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        generate::{box_grid, pipeline, Pipeline},
+        lightmap::{lightmap_charts, pack_lightmap_charts, DEFAULT_LUXEL_SIZE},
+        line::{line_face_connections, line_faces, lines},
+        EPSILON,
+    };
+
+    #[test]
+    fn test_face_lightmap_uvs() {
+        let Pipeline {
+            geo_map,
+            planes,
+            vertices,
+            indices,
+            ..
+        } = pipeline(box_grid([2, 2, 1], 64.0, 0.0));
+        let (lines, face_lines) = lines(&indices);
+        let connections = line_face_connections(&lines, &line_faces(&face_lines), &vertices);
+
+        let (charts, face_charts) = lightmap_charts(
+            &geo_map.faces,
+            &planes,
+            &vertices,
+            &face_lines,
+            &connections,
+            DEFAULT_LUXEL_SIZE,
+        );
+        let (placements, atlases) = pack_lightmap_charts(&charts, 32, 1);
+
+        let uvs = face_lightmap_uvs(&vertices, &face_charts, &charts, &placements, &atlases);
+        assert_eq!(uvs.len(), geo_map.faces.len());
+
+        for (face_id, uvs) in uvs.iter() {
+            let chart_id = &face_charts[face_id];
+            let chart = &charts[chart_id];
+            let placement = &placements[chart_id];
+            let (width, height) = atlases[placement.atlas];
+
+            assert_eq!(uvs.len(), vertices[face_id].len());
+
+            for (vertex, uv) in vertices[face_id].iter().zip(uvs) {
+                assert!(uv.x > 0.0 && uv.x < 1.0 && uv.y > 0.0 && uv.y < 1.0);
+
+                // UVs address the luxel centers of the chart's placement
+                let luxel = nalgebra::vector![uv.x * width as f32, uv.y * height as f32]
+                    - nalgebra::vector![placement.x as f32 + 0.5, placement.y as f32 + 0.5];
+                assert!((luxel - (chart.luxel_coords(vertex) - chart.min)).magnitude() < EPSILON);
+            }
+        }
+
+        // Faces sharing a chart agree on the UV of a shared corner
+        let corner = nalgebra::vector![64.0, 64.0, 64.0];
+        let top_uvs = [4, 10, 16, 22]
+            .iter()
+            .map(|face_id| {
+                let face_id = FaceId(*face_id);
+                let i = vertices[&face_id]
+                    .iter()
+                    .position(|vertex| (vertex - corner).magnitude() < EPSILON)
+                    .unwrap();
+                uvs[&face_id][i]
+            })
+            .collect::<Vec<_>>();
+        for uv in &top_uvs[1..] {
+            assert!((uv - top_uvs[0]).magnitude() < EPSILON);
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use usage::Usage;
+
+use super::{ChartId, ChartPlacements, Light, LightKind, LightmapAtlases, LightmapCharts, Lights};
+use crate::{
+    brush::{BrushHulls, BrushId},
+    Vector3,
+};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LightBakeSettings {
+    /// Light added to every sample regardless of visibility
+    pub ambient: Vector3,
+    /// Blend between flat (0.0) and fully angle-attenuated (1.0) lighting
+    pub angle_scale: f32,
+    /// Distance to push samples off their surface before tracing, avoiding self-shadowing
+    pub surface_offset: f32,
+    /// Maximum distance traced toward suns
+    pub sun_distance: f32,
+}
+
+impl Default for LightBakeSettings {
+    fn default() -> Self {
+        LightBakeSettings {
+            ambient: Vector3::zeros(),
+            angle_scale: 0.5,
+            surface_offset: 1.0,
+            sun_distance: 65536.0,
+        }
+    }
+}
+
+/// Grid of linear RGB light values, where 1.0 corresponds to a Quake light level of 255
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LuxelGrid {
+    pub width: u32,
+    pub height: u32,
+    pub luxels: Vec<Vector3>,
+}
+
+impl LuxelGrid {
+    pub fn get(&self, x: u32, y: u32) -> &Vector3 {
+        &self.luxels[(y * self.width + x) as usize]
+    }
+}
+
+pub enum ChartLuxelsTag {}
+
+pub type ChartLuxels = Usage<ChartLuxelsTag, BTreeMap<ChartId, LuxelGrid>>;
+
+/// Returns true if any hull blocks the segment from `origin` along `direction` for `distance` units
+///
+/// For suns, blocking hulls in `sky_brushes` let light through, as the ray has reached the sky.
+pub fn occluded(
+    brush_hulls: &BrushHulls,
+    sky_brushes: &BTreeSet<BrushId>,
+    origin: &Vector3,
+    direction: &Vector3,
+    distance: f32,
+    sun: bool,
+) -> bool {
+    let nearest = brush_hulls
+        .iter()
+        .filter_map(|(brush_id, hull)| {
+            Some((
+                hull.ray_intersection(origin, direction, distance)?,
+                brush_id,
+            ))
+        })
+        .min_by(|(lhs, _), (rhs, _)| lhs.total_cmp(rhs));
+
+    match nearest {
+        Some((_, brush_id)) => !(sun && sky_brushes.contains(brush_id)),
+        None => false,
+    }
+}
+
+/// Light arriving at `position` on a surface with the given `normal`
+pub fn light_contribution(
+    light: &Light,
+    position: &Vector3,
+    normal: &Vector3,
+    brush_hulls: &BrushHulls,
+    sky_brushes: &BTreeSet<BrushId>,
+    settings: &LightBakeSettings,
+) -> Vector3 {
+    let origin = position + normal * settings.surface_offset;
+
+    let (to_light, distance, value, sun) = match light.kind {
+        LightKind::Sun { direction } => (-direction, settings.sun_distance, light.intensity, true),
+        LightKind::Point | LightKind::Spot { .. } => {
+            let delta = light.position - origin;
+            let distance = delta.magnitude();
+            if distance <= f32::EPSILON {
+                return light.color * light.intensity / 255.0;
+            }
+
+            let to_light = delta / distance;
+
+            if let LightKind::Spot {
+                direction,
+                cone_cos,
+            } = light.kind
+            {
+                if direction.dot(&-to_light) < cone_cos {
+                    return Vector3::zeros();
+                }
+            }
+
+            (to_light, distance, light.attenuate(distance), false)
+        }
+    };
+
+    let lambert = normal.dot(&to_light);
+    if value <= 0.0 || lambert <= 0.0 {
+        return Vector3::zeros();
+    }
+
+    if occluded(brush_hulls, sky_brushes, &origin, &to_light, distance, sun) {
+        return Vector3::zeros();
+    }
+
+    let angle = (1.0 - settings.angle_scale) + settings.angle_scale * lambert;
+    light.color * (value * angle / 255.0)
+}
+
+/// Bake a luxel grid for each chart by tracing shadow rays toward every light
+pub fn bake_lightmaps(
+    charts: &LightmapCharts,
+    lights: &Lights,
+    brush_hulls: &BrushHulls,
+    sky_brushes: &BTreeSet<BrushId>,
+    settings: &LightBakeSettings,
+) -> ChartLuxels {
+    charts
+        .par_iter()
+        .map(|(chart_id, chart)| {
+            let normal = chart.plane.normal();
+
+            let luxels = (0..chart.width * chart.height)
+                .into_par_iter()
+                .map(|i| {
+                    let position = chart.luxel_position(i % chart.width, i / chart.width);
+
+                    lights.iter().fold(settings.ambient, |acc, light| {
+                        acc + light_contribution(
+                            light,
+                            &position,
+                            normal,
+                            brush_hulls,
+                            sky_brushes,
+                            settings,
+                        )
+                    })
+                })
+                .collect();
+
+            (
+                *chart_id,
+                LuxelGrid {
+                    width: chart.width,
+                    height: chart.height,
+                    luxels,
+                },
+            )
+        })
+        .collect()
+}
+
+/// Compose baked charts into their atlases for preview or upload
+///
+/// Returns one row-major RGB grid per atlas, with unused luxels left black.
+pub fn lightmap_atlas_luxels(
+    chart_luxels: &ChartLuxels,
+    placements: &ChartPlacements,
+    atlases: &LightmapAtlases,
+) -> Vec<LuxelGrid> {
+    let mut grids = atlases
+        .iter()
+        .map(|(width, height)| LuxelGrid {
+            width: *width,
+            height: *height,
+            luxels: vec![Vector3::zeros(); (width * height) as usize],
+        })
+        .collect::<Vec<_>>();
+
+    for (chart_id, luxels) in chart_luxels.iter() {
+        let placement = &placements[chart_id];
+        let grid = &mut grids[placement.atlas];
+
+        for y in 0..luxels.height {
+            for x in 0..luxels.width {
+                let i = (placement.y + y) * grid.width + placement.x + x;
+                grid.luxels[i as usize] = *luxels.get(x, y);
+            }
+        }
+    }
+
+    grids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::{box_brush, pipeline, worldspawn};
+
+    #[test]
+    fn test_occluded() {
+        let hulls = pipeline(worldspawn(vec![
+            box_brush(
+                nalgebra::vector![0.0, 0.0, 0.0],
+                nalgebra::vector![64.0, 64.0, 64.0],
+            ),
+            box_brush(
+                nalgebra::vector![0.0, 0.0, 128.0],
+                nalgebra::vector![64.0, 64.0, 144.0],
+            ),
+        ]))
+        .hulls;
+
+        let (min, max) = hulls[&BrushId(1)].bounds().unwrap();
+        assert!((min - nalgebra::vector![0.0, 0.0, 128.0]).magnitude() < 0.01);
+        assert!((max - nalgebra::vector![64.0, 64.0, 144.0]).magnitude() < 0.01);
+
+        let no_sky = BTreeSet::default();
+        let sky = std::iter::once(BrushId(1)).collect::<BTreeSet<_>>();
+        let origin = nalgebra::vector![32.0, 32.0, 96.0];
+        let (down, side) = (-Vector3::z(), Vector3::x());
+
+        // Blocked by the box below, clear to the side
+        assert!(occluded(&hulls, &no_sky, &origin, &down, 64.0, false));
+        assert!(!occluded(&hulls, &no_sky, &origin, &down, 16.0, false));
+        assert!(!occluded(&hulls, &no_sky, &origin, &side, 256.0, false));
+
+        // Sky brushes only let suns through
+        assert!(occluded(&hulls, &sky, &origin, &Vector3::z(), 256.0, false));
+        assert!(!occluded(&hulls, &sky, &origin, &Vector3::z(), 256.0, true));
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::BTreeMap;
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use usage::Usage;
+
+use super::ChartId;
+use crate::{
+    face::{FaceId, FaceLines, FacePlanes, FaceVertices},
+    line::LineFaceConnections,
+    Plane3d, Vector2, Vector3,
+};
+
+/// A group of coplanar, edge-connected faces sharing a single lightmap rectangle
+#[derive(Debug, Clone, PartialEq)]
+pub struct LightmapChart {
+    pub faces: Vec<FaceId>,
+    pub plane: Plane3d,
+    pub u_axis: Vector3,
+    pub v_axis: Vector3,
+    /// World units per luxel
+    pub luxel_size: f32,
+    /// Luxel-space coordinate of luxel (0, 0)
+    pub min: Vector2,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl LightmapChart {
+    /// Project a world-space position into this chart's luxel space
+    pub fn luxel_coords(&self, position: &Vector3) -> Vector2 {
+        nalgebra::vector![
+            position.dot(&self.u_axis) / self.luxel_size,
+            position.dot(&self.v_axis) / self.luxel_size
+        ]
+    }
+
+    /// World-space sample position of the luxel at (x, y)
+    pub fn luxel_position(&self, x: u32, y: u32) -> Vector3 {
+        let u = (self.min.x + x as f32) * self.luxel_size;
+        let v = (self.min.y + y as f32) * self.luxel_size;
+        self.u_axis * u + self.v_axis * v + self.plane.normal() * self.plane.distance()
+    }
+}
+
+pub enum LightmapChartsTag {}
+pub enum FaceChartsTag {}
+
+pub type LightmapCharts = Usage<LightmapChartsTag, BTreeMap<ChartId, LightmapChart>>;
+pub type FaceCharts = Usage<FaceChartsTag, BTreeMap<FaceId, ChartId>>;
+
+/// Group coplanar faces that share an edge into charts, and size each chart at the given luxel density
+pub fn lightmap_charts(
+    faces: &Vec<FaceId>,
+    face_planes: &FacePlanes,
+    face_vertices: &FaceVertices,
+    face_lines: &FaceLines,
+    line_face_connections: &LineFaceConnections,
+    luxel_size: f32,
+) -> (LightmapCharts, FaceCharts) {
+    // Union coplanar faces connected by a line
+    let mut parents = faces
+        .iter()
+        .map(|face_id| (*face_id, *face_id))
+        .collect::<BTreeMap<_, _>>();
+
+    fn root(parents: &mut BTreeMap<FaceId, FaceId>, face_id: FaceId) -> FaceId {
+        let parent = parents[&face_id];
+        if parent == face_id {
+            return face_id;
+        }
+        let root_id = root(parents, parent);
+        parents.insert(face_id, root_id);
+        root_id
+    }
+
+    for face_id in faces {
+        let plane = &face_planes[face_id];
+
+        for line_id in face_lines.get(face_id).into_iter().flatten() {
+            for connected_id in &line_face_connections[line_id] {
+                if connected_id == face_id || !plane.coplanar(&face_planes[connected_id]) {
+                    continue;
+                }
+
+                let lhs = root(&mut parents, *face_id);
+                let rhs = root(&mut parents, *connected_id);
+                if lhs != rhs {
+                    parents.insert(lhs.max(rhs), lhs.min(rhs));
+                }
+            }
+        }
+    }
+
+    let mut groups = BTreeMap::<FaceId, Vec<FaceId>>::default();
+    for face_id in faces {
+        let root_id = root(&mut parents, *face_id);
+        groups.entry(root_id).or_default().push(*face_id);
+    }
+
+    let charts: LightmapCharts = groups
+        .into_values()
+        .enumerate()
+        .collect::<Vec<_>>()
+        .into_par_iter()
+        .map(|(i, faces)| {
+            let plane = face_planes[&faces[0]];
+            let (u_axis, v_axis) = plane.axes();
+
+            let mut chart = LightmapChart {
+                faces,
+                plane,
+                u_axis,
+                v_axis,
+                luxel_size,
+                min: Vector2::zeros(),
+                width: 0,
+                height: 0,
+            };
+
+            let mut min = Vector2::repeat(f32::INFINITY);
+            let mut max = Vector2::repeat(f32::NEG_INFINITY);
+            for face_id in &chart.faces {
+                for vertex in &face_vertices[face_id] {
+                    let coords = chart.luxel_coords(vertex);
+                    min = min.inf(&coords);
+                    max = max.sup(&coords);
+                }
+            }
+
+            if min.x <= max.x && min.y <= max.y {
+                chart.min = nalgebra::vector![min.x.floor(), min.y.floor()];
+                chart.width = (max.x.ceil() - chart.min.x) as u32 + 1;
+                chart.height = (max.y.ceil() - chart.min.y) as u32 + 1;
+            }
+
+            (ChartId(i), chart)
+        })
+        .collect();
+
+    let face_charts = charts
+        .iter()
+        .flat_map(|(chart_id, chart)| chart.faces.iter().map(move |face_id| (*face_id, *chart_id)))
+        .collect();
+
+    (charts, face_charts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        generate::{box_grid, pipeline, Pipeline},
+        lightmap::DEFAULT_LUXEL_SIZE,
+        line::{line_face_connections, line_faces, lines},
+        EPSILON,
+    };
+
+    #[test]
+    fn test_lightmap_charts() {
+        // Two touching boxes, so their top, bottom and side faces pair up into shared charts
+        let Pipeline {
+            geo_map,
+            planes,
+            vertices,
+            indices,
+            ..
+        } = pipeline(box_grid([2, 1, 1], 64.0, 0.0));
+        let (lines, face_lines) = lines(&indices);
+        let connections = line_face_connections(&lines, &line_faces(&face_lines), &vertices);
+
+        let (charts, face_charts) = lightmap_charts(
+            &geo_map.faces,
+            &planes,
+            &vertices,
+            &face_lines,
+            &connections,
+            DEFAULT_LUXEL_SIZE,
+        );
+
+        // The touching +X and -X faces oppose rather than share a plane, so stay separate
+        assert_eq!(charts.len(), 8);
+        assert_eq!(face_charts.len(), 12);
+        for (lhs, rhs) in [(2, 8), (3, 9), (4, 10), (5, 11)].iter() {
+            assert_eq!(face_charts[&FaceId(*lhs)], face_charts[&FaceId(*rhs)]);
+        }
+        assert_ne!(face_charts[&FaceId(0)], face_charts[&FaceId(7)]);
+        assert_ne!(face_charts[&FaceId(4)], face_charts[&FaceId(5)]);
+
+        for (chart_id, chart) in charts.iter() {
+            for face_id in &chart.faces {
+                assert_eq!(face_charts[face_id], *chart_id);
+
+                // Every vertex falls within the chart's luxel rectangle
+                for vertex in &vertices[face_id] {
+                    let coords = chart.luxel_coords(vertex) - chart.min;
+                    assert!(coords.x >= 0.0 && coords.x <= (chart.width - 1) as f32);
+                    assert!(coords.y >= 0.0 && coords.y <= (chart.height - 1) as f32);
+                }
+            }
+
+            // Luxel positions lie on the chart plane and map back to their luxel
+            let position = chart.luxel_position(1, 2);
+            assert!((chart.plane.normal().dot(&position) - chart.plane.distance()).abs() < EPSILON);
+            let coords = chart.luxel_coords(&position) - chart.min;
+            assert!((coords - nalgebra::vector![1.0, 2.0]).magnitude() < EPSILON);
+        }
+
+        // The shared top chart spans 128 x 64 units, or 8 x 4 luxels plus the trailing edge.
+        // Axis-aligned vertices project to whole luxels, so the size is exact
+        let top = &charts[&face_charts[&FaceId(4)]];
+        let mut size = [top.width, top.height];
+        size.sort_unstable();
+        assert_eq!(size, [5, 9]);
+    }
+}
@@ -0,0 +1,220 @@
+use std::collections::BTreeMap;
+
+use usage::Usage;
+
+use super::{ChartId, LightmapCharts};
+
+/// Location of a chart's first luxel within a lightmap atlas
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LightmapPlacement {
+    pub atlas: usize,
+    pub x: u32,
+    pub y: u32,
+}
+
+pub enum ChartPlacementsTag {}
+pub enum LightmapAtlasesTag {}
+
+pub type ChartPlacements = Usage<ChartPlacementsTag, BTreeMap<ChartId, LightmapPlacement>>;
+
+/// Width and height of each lightmap atlas in luxels
+pub type LightmapAtlases = Usage<LightmapAtlasesTag, Vec<(u32, u32)>>;
+
+#[derive(Default)]
+struct Shelf {
+    y: u32,
+    height: u32,
+    x: u32,
+}
+
+#[derive(Default)]
+struct Atlas {
+    shelves: Vec<Shelf>,
+    y: u32,
+}
+
+impl Atlas {
+    fn insert(&mut self, width: u32, height: u32, size: u32) -> Option<(u32, u32)> {
+        for shelf in &mut self.shelves {
+            if height <= shelf.height && shelf.x + width <= size {
+                let position = (shelf.x, shelf.y);
+                shelf.x += width;
+                return Some(position);
+            }
+        }
+
+        if self.y + height > size || width > size {
+            return None;
+        }
+
+        self.shelves.push(Shelf {
+            y: self.y,
+            height,
+            x: width,
+        });
+        let position = (0, self.y);
+        self.y += height;
+        Some(position)
+    }
+}
+
+/// Pack charts into square atlases of `atlas_size` luxels using shelf packing
+///
+/// Each chart is surrounded by `padding` luxels to prevent bleeding when filtered.
+/// Charts too large for a single atlas are given a dedicated atlas of their own size.
+pub fn pack_lightmap_charts(
+    charts: &LightmapCharts,
+    atlas_size: u32,
+    padding: u32,
+) -> (ChartPlacements, LightmapAtlases) {
+    let mut order = charts
+        .iter()
+        .map(|(chart_id, chart)| {
+            (
+                *chart_id,
+                chart.width + padding * 2,
+                chart.height + padding * 2,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    // Tallest first keeps shelves tightly filled
+    order.sort_unstable_by(|(lhs_id, lhs_w, lhs_h), (rhs_id, rhs_w, rhs_h)| {
+        rhs_h
+            .cmp(lhs_h)
+            .then(rhs_w.cmp(lhs_w))
+            .then(lhs_id.cmp(rhs_id))
+    });
+
+    // Dedicated atlases for oversized charts are closed to further insertion
+    let mut atlases = Vec::<Option<Atlas>>::default();
+    let mut sizes = LightmapAtlases::default();
+    let mut placements = ChartPlacements::default();
+
+    for (chart_id, width, height) in order {
+        if width > atlas_size || height > atlas_size {
+            placements.insert(
+                chart_id,
+                LightmapPlacement {
+                    atlas: sizes.len(),
+                    x: padding,
+                    y: padding,
+                },
+            );
+            atlases.push(None);
+            sizes.push((width, height));
+            continue;
+        }
+
+        let placed = atlases
+            .iter_mut()
+            .enumerate()
+            .find_map(|(i, atlas)| Some((i, atlas.as_mut()?.insert(width, height, atlas_size)?)));
+
+        let (atlas, (x, y)) = match placed {
+            Some(placed) => placed,
+            None => {
+                let mut atlas = Atlas::default();
+                let position = atlas.insert(width, height, atlas_size).unwrap();
+                atlases.push(Some(atlas));
+                sizes.push((atlas_size, atlas_size));
+                (atlases.len() - 1, position)
+            }
+        };
+
+        placements.insert(
+            chart_id,
+            LightmapPlacement {
+                atlas,
+                x: x + padding,
+                y: y + padding,
+            },
+        );
+    }
+
+    (placements, sizes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lightmap::LightmapChart, Plane3d, Vector2, Vector3};
+
+    fn chart(width: u32, height: u32) -> LightmapChart {
+        LightmapChart {
+            faces: vec![],
+            plane: Plane3d {
+                n: Vector3::z(),
+                d: 0.0,
+            },
+            u_axis: Vector3::x(),
+            v_axis: Vector3::y(),
+            luxel_size: 16.0,
+            min: Vector2::zeros(),
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn test_pack_lightmap_charts() {
+        let (atlas_size, padding) = (64, 1);
+
+        // Assorted sizes that need several atlases, plus one too large for any atlas
+        let sizes = (0..40)
+            .map(|i| (1 + i * 7 % 23, 1 + i * 11 % 17))
+            .chain(std::iter::once((100, 8)))
+            .collect::<Vec<_>>();
+
+        let charts: LightmapCharts = sizes
+            .iter()
+            .enumerate()
+            .map(|(i, (width, height))| (ChartId(i), chart(*width, *height)))
+            .collect();
+
+        let (placements, atlases) = pack_lightmap_charts(&charts, atlas_size, padding);
+        assert_eq!(placements.len(), charts.len());
+        assert!(atlases.len() > 2);
+
+        // Padded rectangles as (atlas, min, max)
+        let rects = placements
+            .iter()
+            .map(|(chart_id, placement)| {
+                let chart = &charts[chart_id];
+                let min = (placement.x - padding, placement.y - padding);
+                let max = (
+                    placement.x + chart.width + padding,
+                    placement.y + chart.height + padding,
+                );
+                (placement.atlas, min, max)
+            })
+            .collect::<Vec<_>>();
+
+        for (atlas, _, max) in &rects {
+            let (width, height) = atlases[*atlas];
+            assert!(max.0 <= width && max.1 <= height);
+        }
+
+        for (i, (lhs_atlas, lhs_min, lhs_max)) in rects.iter().enumerate() {
+            for (rhs_atlas, rhs_min, rhs_max) in &rects[i + 1..] {
+                let overlaps = lhs_atlas == rhs_atlas
+                    && lhs_min.0 < rhs_max.0
+                    && rhs_min.0 < lhs_max.0
+                    && lhs_min.1 < rhs_max.1
+                    && rhs_min.1 < lhs_max.1;
+                assert!(!overlaps);
+            }
+        }
+
+        // The oversized chart gets an atlas of its own, sized to fit
+        let oversized = placements[&ChartId(40)];
+        assert_eq!(atlases[oversized.atlas], (102, 10));
+        assert_eq!(
+            placements
+                .values()
+                .filter(|placement| placement.atlas == oversized.atlas)
+                .count(),
+            1
+        );
+    }
+}
@@ -0,0 +1,355 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use usage::Usage;
+
+use crate::{
+    entity::{entity_property, entity_property_vector3, EntityId},
+    EntityProperties, Vector3,
+};
+
+/// Quake light falloff, selected by the `delay` key
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LightFalloff {
+    Linear,
+    Inverse,
+    InverseSquare,
+    None,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum LightKind {
+    /// Omnidirectional light
+    Point,
+    /// Cone of light along `direction`, with the cosine of its half-angle
+    Spot { direction: Vector3, cone_cos: f32 },
+    /// Parallel light travelling along `direction` from the sky
+    Sun { direction: Vector3 },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Light {
+    pub kind: LightKind,
+    pub position: Vector3,
+    pub intensity: f32,
+    pub color: Vector3,
+    /// Falloff distance scale
+    pub wait: f32,
+    pub falloff: LightFalloff,
+}
+
+impl Light {
+    /// Light value reaching a point `distance` units away, before angle attenuation and shadowing
+    pub fn attenuate(&self, distance: f32) -> f32 {
+        const FALLOFF_SCALE: f32 = 128.0;
+
+        let scaled = distance * self.wait;
+        let value = match self.falloff {
+            LightFalloff::Linear => self.intensity - scaled,
+            LightFalloff::Inverse => self.intensity / (scaled / FALLOFF_SCALE),
+            LightFalloff::InverseSquare => self.intensity / (scaled / FALLOFF_SCALE).powi(2),
+            LightFalloff::None => self.intensity,
+        };
+
+        value.max(0.0).min(self.intensity.max(0.0))
+    }
+}
+
+pub enum LightsTag {}
+
+pub type Lights = Usage<LightsTag, Vec<Light>>;
+
+const DEFAULT_LIGHT: f32 = 300.0;
+const DEFAULT_SPOT_ANGLE: f32 = 40.0;
+
+/// Parse `light*` point entities and worldspawn sunlight into a list of lights
+///
+/// Supports `light` / `_light`, `_color`, `wait`, `delay`, and spotlights via `mangle` or `target`
+/// with `angle` as the cone width in degrees. Lights with `_sun 1` become suns along their `mangle`,
+/// as does worldspawn `_sunlight` with `_sun_mangle` and `_sunlight_color`.
+pub fn lights(
+    entities: &[EntityId],
+    point_entities: &[EntityId],
+    entity_properties: &EntityProperties,
+) -> Lights {
+    let mut lights = Lights::default();
+
+    let point_entities = point_entities.iter().collect::<BTreeSet<_>>();
+
+    // Spotlight targets, keyed by targetname.
+    // Reversed so the first entity wins when a targetname is shared
+    let targets = entity_properties
+        .values()
+        .rev()
+        .filter_map(|properties| {
+            Some((
+                entity_property(properties, "targetname")?,
+                entity_property_vector3(properties, "origin")?,
+            ))
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    for entity_id in entities {
+        let properties = &entity_properties[entity_id];
+        let classname = entity_property(properties, "classname").unwrap_or_default();
+
+        if classname == "worldspawn" {
+            let intensity = parse_f32(entity_property(properties, "_sunlight"));
+            if let Some(intensity) = intensity.filter(|intensity| *intensity > 0.0) {
+                let direction = entity_property_vector3(properties, "_sun_mangle")
+                    .map(mangle_direction)
+                    .unwrap_or_else(|| -Vector3::z());
+
+                lights.push(Light {
+                    kind: LightKind::Sun { direction },
+                    position: Vector3::zeros(),
+                    intensity,
+                    color: parse_color(entity_property(properties, "_sunlight_color")),
+                    wait: 1.0,
+                    falloff: LightFalloff::None,
+                });
+            }
+            continue;
+        }
+
+        if !classname.starts_with("light") || !point_entities.contains(entity_id) {
+            continue;
+        }
+
+        let position = entity_property_vector3(properties, "origin").unwrap_or_else(Vector3::zeros);
+
+        let intensity = parse_f32(entity_property(properties, "light"))
+            .or_else(|| parse_f32(entity_property(properties, "_light")))
+            .unwrap_or(DEFAULT_LIGHT);
+
+        let falloff = match entity_property(properties, "delay").map(str::trim) {
+            Some("1") => LightFalloff::Inverse,
+            Some("2") => LightFalloff::InverseSquare,
+            Some("3") => LightFalloff::None,
+            _ => LightFalloff::Linear,
+        };
+
+        let wait = parse_f32(entity_property(properties, "wait"))
+            .filter(|wait| *wait > 0.0)
+            .unwrap_or(1.0);
+
+        let mangle = entity_property_vector3(properties, "mangle").map(mangle_direction);
+
+        let kind = if parse_f32(entity_property(properties, "_sun")) == Some(1.0) {
+            LightKind::Sun {
+                direction: mangle.unwrap_or_else(|| -Vector3::z()),
+            }
+        } else if let Some(direction) =
+            mangle.or_else(|| target_direction(&targets, properties, &position))
+        {
+            let angle =
+                parse_f32(entity_property(properties, "angle")).unwrap_or(DEFAULT_SPOT_ANGLE);
+            LightKind::Spot {
+                direction,
+                cone_cos: (angle * 0.5).to_radians().cos(),
+            }
+        } else {
+            LightKind::Point
+        };
+
+        lights.push(Light {
+            kind,
+            position,
+            intensity,
+            color: parse_color(entity_property(properties, "_color")),
+            wait,
+            falloff,
+        });
+    }
+
+    lights
+}
+
+fn parse_f32(value: Option<&str>) -> Option<f32> {
+    value?.trim().parse().ok()
+}
+
+/// Parse a `r g b` color given either in 0-1 or 0-255 range
+fn parse_color(value: Option<&str>) -> Vector3 {
+    let components = value
+        .unwrap_or_default()
+        .split_whitespace()
+        .filter_map(|component| component.parse::<f32>().ok())
+        .collect::<Vec<_>>();
+
+    let color = match components[..] {
+        [r, g, b, ..] => nalgebra::vector![r, g, b],
+        _ => return Vector3::repeat(1.0),
+    };
+
+    if color.iter().any(|component| *component > 1.0) {
+        color / 255.0
+    } else {
+        color
+    }
+}
+
+/// Convert a `yaw pitch roll` mangle in degrees into a direction
+fn mangle_direction(mangle: Vector3) -> Vector3 {
+    let yaw = mangle.x.to_radians();
+    let pitch = mangle.y.to_radians();
+    nalgebra::vector![
+        yaw.cos() * pitch.cos(),
+        yaw.sin() * pitch.cos(),
+        pitch.sin()
+    ]
+}
+
+fn target_direction(
+    targets: &BTreeMap<&str, Vector3>,
+    properties: &shalrath::repr::Properties,
+    position: &Vector3,
+) -> Option<Vector3> {
+    let target_position = targets.get(entity_property(properties, "target")?)?;
+    (target_position - position).try_normalize(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{generate::entity, GeoMap};
+    use shalrath::repr::Map;
+
+    fn approx(lhs: Vector3, rhs: Vector3) -> bool {
+        (lhs - rhs).magnitude() < 0.001
+    }
+
+    #[test]
+    fn test_lights() {
+        let geo_map = GeoMap::new(Map(vec![
+            entity(
+                &[
+                    ("classname", "worldspawn"),
+                    ("_sunlight", "150"),
+                    ("_sun_mangle", "0 -90 0"),
+                    ("_sunlight_color", "255 128 0"),
+                ],
+                vec![],
+            ),
+            entity(
+                &[("classname", "light"), ("origin", "1 2 3"), ("light", "200")],
+                vec![],
+            ),
+            entity(
+                &[
+                    ("classname", "light_fluoro"),
+                    ("_light", "100"),
+                    ("delay", "2"),
+                    ("wait", "0.5"),
+                    ("_color", "0.5 0.25 1"),
+                ],
+                vec![],
+            ),
+            entity(
+                &[
+                    ("classname", "light"),
+                    ("origin", "0 0 64"),
+                    ("target", "spot"),
+                    ("angle", "60"),
+                ],
+                vec![],
+            ),
+            entity(
+                &[("classname", "info_null"), ("targetname", "spot"), ("origin", "0 0 0")],
+                vec![],
+            ),
+            entity(
+                &[("classname", "light"), ("_sun", "1"), ("mangle", "90 0 0")],
+                vec![],
+            ),
+            // Brush entities are never lights
+            entity(
+                &[("classname", "light"), ("light", "500")],
+                vec![crate::generate::box_brush(
+                    Vector3::zeros(),
+                    nalgebra::vector![16.0, 16.0, 16.0],
+                )],
+            ),
+        ]));
+
+        let lights = lights(
+            &geo_map.entities,
+            &geo_map.point_entities,
+            &geo_map.entity_properties,
+        );
+        assert_eq!(lights.len(), 5);
+
+        let sun = &lights[0];
+        assert_eq!(sun.intensity, 150.0);
+        assert_eq!(sun.falloff, LightFalloff::None);
+        assert!(approx(sun.color, nalgebra::vector![1.0, 128.0 / 255.0, 0.0]));
+        match sun.kind {
+            LightKind::Sun { direction } => assert!(approx(direction, -Vector3::z())),
+            kind => panic!("expected a sun, got {:?}", kind),
+        }
+
+        let point = &lights[1];
+        assert_eq!(point.kind, LightKind::Point);
+        assert_eq!(point.position, nalgebra::vector![1.0, 2.0, 3.0]);
+        assert_eq!(point.intensity, 200.0);
+        assert_eq!(point.color, Vector3::repeat(1.0));
+        assert_eq!(point.wait, 1.0);
+        assert_eq!(point.falloff, LightFalloff::Linear);
+
+        let fluoro = &lights[2];
+        assert_eq!(fluoro.position, Vector3::zeros());
+        assert_eq!(fluoro.intensity, 100.0);
+        assert_eq!(fluoro.falloff, LightFalloff::InverseSquare);
+        assert_eq!(fluoro.wait, 0.5);
+        assert_eq!(fluoro.color, nalgebra::vector![0.5, 0.25, 1.0]);
+
+        let spot = &lights[3];
+        assert_eq!(spot.intensity, DEFAULT_LIGHT);
+        match spot.kind {
+            LightKind::Spot {
+                direction,
+                cone_cos,
+            } => {
+                assert!(approx(direction, -Vector3::z()));
+                assert!((cone_cos - 30.0f32.to_radians().cos()).abs() < 0.001);
+            }
+            kind => panic!("expected a spotlight, got {:?}", kind),
+        }
+
+        match lights[4].kind {
+            LightKind::Sun { direction } => assert!(approx(direction, Vector3::y())),
+            kind => panic!("expected a sun, got {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn test_attenuate() {
+        let light = |falloff, wait| Light {
+            kind: LightKind::Point,
+            position: Vector3::zeros(),
+            intensity: 200.0,
+            color: Vector3::repeat(1.0),
+            wait,
+            falloff,
+        };
+
+        let linear = light(LightFalloff::Linear, 1.0);
+        assert_eq!(linear.attenuate(0.0), 200.0);
+        assert_eq!(linear.attenuate(50.0), 150.0);
+        assert_eq!(linear.attenuate(300.0), 0.0);
+
+        // Wait scales distance
+        assert_eq!(light(LightFalloff::Linear, 0.5).attenuate(100.0), 150.0);
+
+        let inverse = light(LightFalloff::Inverse, 1.0);
+        assert_eq!(inverse.attenuate(256.0), 100.0);
+        // Clamped to the light's intensity close to the source
+        assert_eq!(inverse.attenuate(1.0), 200.0);
+
+        let inverse_square = light(LightFalloff::InverseSquare, 1.0);
+        assert_eq!(inverse_square.attenuate(256.0), 50.0);
+        assert_eq!(inverse_square.attenuate(0.0), 200.0);
+
+        let none = light(LightFalloff::None, 1.0);
+        assert_eq!(none.attenuate(10000.0), 200.0);
+    }
+}
@@ -0,0 +1,23 @@
+mod face_lightmap_uvs;
+mod lightmap_bake;
+mod lightmap_charts;
+mod lightmap_packing;
+mod lights;
+
+pub use face_lightmap_uvs::*;
+pub use lightmap_bake::*;
+pub use lightmap_charts::*;
+pub use lightmap_packing::*;
+pub use lights::*;
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ChartId(pub usize);
+
+impl std::fmt::Display for ChartId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(&self.0, f)
+    }
+}
+
+/// Quake-style lightmap resolution in world units per luxel
+pub const DEFAULT_LUXEL_SIZE: f32 = 16.0;
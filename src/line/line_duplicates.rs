@@ -1,17 +1,30 @@
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use usage::Usage;
 
 use super::{line_eq, LineId, Lines};
 
 use crate::{
     face::{FaceDuplicates, FaceLines, FaceVertices},
-    BrushFaces, Brushes,
+    BrushFaces, Brushes, Vector3, EPSILON,
 };
 
 pub enum LineDuplicatesTag {}
 pub type LineDuplicates = Usage<LineDuplicatesTag, BTreeSet<(LineId, LineId)>>;
 
+/// Size of the grid line endpoints are snapped to for bucketing
+///
+/// Cells are centered on multiples of this size, so endpoints on a regular grid
+/// rarely fall within [`EPSILON`] of a cell boundary.
+const CELL: f32 = 1.0 / 16.0;
+
+type Cell = [i64; 3];
+
+/// Find equal lines belonging to different brushes, skipping faces with a duplicate
+///
+/// Lines are bucketed by the grid cells of their endpoints,
+/// so only lines with coincident endpoints are compared.
+/// Produces the same result as comparing every pair of lines.
 pub fn line_duplicates(
     brushes: &Brushes,
     lines: &Lines,
@@ -19,6 +32,123 @@ pub fn line_duplicates(
     face_duplicates: &FaceDuplicates,
     face_vertices: &FaceVertices,
     face_lines: &FaceLines,
+) -> LineDuplicates {
+    let duplicate_faces = face_duplicates
+        .iter()
+        .map(|(face_id, _)| *face_id)
+        .collect::<HashSet<_>>();
+
+    let segments = brushes
+        .iter()
+        .flat_map(|brush_id| {
+            brush_faces[brush_id]
+                .iter()
+                .filter(|face_id| !duplicate_faces.contains(face_id))
+                .flat_map(move |face_id| {
+                    let vertices = &face_vertices[face_id];
+                    face_lines[face_id].iter().map(move |line_id| {
+                        let line = lines[line_id];
+                        (*brush_id, *line_id, vertices[line.i0], vertices[line.i1])
+                    })
+                })
+        })
+        .collect::<Vec<_>>();
+
+    let mut buckets = HashMap::<(Cell, Cell), Vec<usize>>::default();
+    for (i, (_, _, v0, v1)) in segments.iter().enumerate() {
+        buckets
+            .entry(edge_key(cell(v0), cell(v1)))
+            .or_default()
+            .push(i);
+    }
+
+    segments
+        .par_iter()
+        .flat_map(|(lhs_brush, lhs_id, lhs_v0, lhs_v1)| {
+            let mut duplicates = vec![];
+
+            // Endpoints near a cell boundary may have been bucketed in the neighbouring cell
+            for c0 in cells(lhs_v0) {
+                for c1 in cells(lhs_v1) {
+                    let bucket = match buckets.get(&edge_key(c0, c1)) {
+                        Some(bucket) => bucket,
+                        None => continue,
+                    };
+
+                    for (rhs_brush, rhs_id, rhs_v0, rhs_v1) in bucket.iter().map(|i| &segments[*i])
+                    {
+                        // Skip comparing with lines of the same brush
+                        if lhs_brush == rhs_brush {
+                            continue;
+                        }
+
+                        if line_eq(lhs_v0, lhs_v1, rhs_v0, rhs_v1) {
+                            duplicates.push((*lhs_id, *rhs_id));
+                        }
+                    }
+                }
+            }
+
+            duplicates
+        })
+        .collect()
+}
+
+/// Grid cell nearest to a point
+fn cell(v: &Vector3) -> Cell {
+    let snap = |value: f32| (value / CELL).round() as i64;
+    [snap(v.x), snap(v.y), snap(v.z)]
+}
+
+/// Every grid cell within [`EPSILON`] of a point, starting with the nearest
+fn cells(v: &Vector3) -> Vec<Cell> {
+    let tolerance = EPSILON / CELL;
+
+    let axis_cells = |value: f32| {
+        let scaled = value / CELL;
+        let nearest = scaled.round();
+        let offset = scaled - nearest;
+
+        let mut cells = vec![nearest as i64];
+        if offset.abs() > 0.5 - tolerance {
+            cells.push((nearest + offset.signum()) as i64);
+        }
+        cells
+    };
+
+    let xs = axis_cells(v.x);
+    let ys = axis_cells(v.y);
+    let zs = axis_cells(v.z);
+
+    let mut cells = vec![];
+    for x in &xs {
+        for y in &ys {
+            for z in &zs {
+                cells.push([*x, *y, *z]);
+            }
+        }
+    }
+    cells
+}
+
+/// Bucket key for a line, independent of its direction
+fn edge_key(c0: Cell, c1: Cell) -> (Cell, Cell) {
+    if c0 <= c1 {
+        (c0, c1)
+    } else {
+        (c1, c0)
+    }
+}
+
+/// Reference implementation of [`line_duplicates`] comparing every pair of lines
+#[cfg(test)]
+fn line_duplicates_exhaustive(
+    brushes: &Brushes,
+    lines: &Lines,
+    brush_faces: &BrushFaces,
+    face_duplicates: &FaceDuplicates,
+    face_vertices: &FaceVertices,
+    face_lines: &FaceLines,
 ) -> LineDuplicates {
     brushes
         .par_iter()
@@ -97,3 +227,66 @@ pub fn line_duplicates(
         })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        face::face_duplicates,
+        generate::{self, pipeline, Pipeline},
+        line::lines,
+    };
+
+    /// Compare against the reference implementation, returning the result
+    fn assert_matches_exhaustive(map: shalrath::repr::Map) -> LineDuplicates {
+        let Pipeline {
+            geo_map,
+            planes,
+            vertices,
+            indices,
+            ..
+        } = pipeline(map);
+        let (lines, face_lines) = lines(&indices);
+        let face_duplicates = face_duplicates(&geo_map.faces, &planes, &vertices);
+
+        let expected = line_duplicates_exhaustive(
+            &geo_map.brushes,
+            &lines,
+            &geo_map.brush_faces,
+            &face_duplicates,
+            &vertices,
+            &face_lines,
+        );
+        let actual = line_duplicates(
+            &geo_map.brushes,
+            &lines,
+            &geo_map.brush_faces,
+            &face_duplicates,
+            &vertices,
+            &face_lines,
+        );
+
+        assert_eq!(*actual, *expected);
+        actual
+    }
+
+    #[test]
+    fn test_box_grid() {
+        assert!(!assert_matches_exhaustive(generate::box_grid([3, 3, 2], 64.0, 0.0)).is_empty());
+    }
+
+    #[test]
+    fn test_staircase() {
+        assert!(!assert_matches_exhaustive(generate::staircase(16)).is_empty());
+    }
+
+    #[test]
+    fn test_overlapping_boxes() {
+        assert_matches_exhaustive(generate::overlapping_boxes(24, 7));
+    }
+
+    #[test]
+    fn test_large_world() {
+        assert!(!assert_matches_exhaustive(generate::large_world(2)).is_empty());
+    }
+}
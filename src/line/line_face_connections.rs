@@ -1,17 +1,190 @@
 //! Lookup table from LineId to the FaceIds it connects to
-use std::collections::{BTreeMap, BTreeSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
-use crate::face::{FaceId, FaceVertices};
-use usage::{AsUsage, Usage};
+use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
+#[cfg(any(test, feature = "bench"))]
+use usage::AsUsage;
+use usage::Usage;
+
+use crate::{
+    face::{FaceId, FaceVertices},
+    Plane3d, Vector3, EPSILON,
+};
 
 use super::{point_in_line, LineFaces, LineId, Lines};
 pub enum LineFaceConnectionsTag {}
 pub type LineFaceConnections = Usage<LineFaceConnectionsTag, BTreeMap<LineId, BTreeSet<FaceId>>>;
 
+/// Lines shorter than this are compared against every other line,
+/// since the collinearity test in `point_in_line` allows their directions to diverge beyond the bucket tolerances
+const MIN_BUCKET_LENGTH: f32 = 1.0;
+
+/// Size of direction buckets, in units of a normalized direction vector
+const DIRECTION_CELL: f32 = 1.0 / 32.0;
+
+/// Directions closer than this share a bucket,
+/// covering the collinearity test in `point_in_line` for segments of at least `MIN_BUCKET_LENGTH`
+const DIRECTION_TOLERANCE: f32 = 2.0 * EPSILON;
+
+/// Size of position buckets, measured perpendicular to the bucket direction
+const POSITION_CELL: f32 = 32.0;
+
+const POSITION_TOLERANCE: f32 = 2.0 * EPSILON;
+
+type BucketKey = ([i32; 3], [i64; 2]);
+
+/// Find the faces connected to each line
+///
+/// Lines are bucketed by direction and by position perpendicular to that direction,
+/// so only lines that could be collinear are compared.
+/// Lines shorter than one unit are compared against every other line.
+/// Produces the same result as `line_face_connections_exhaustive`.
 pub fn line_face_connections(
     lines: &Lines,
     line_faces: &LineFaces,
     face_vertices: &FaceVertices,
+) -> LineFaceConnections {
+    let segments = lines
+        .par_iter()
+        .map(|(line_id, line)| {
+            let face_id = line_faces[line_id];
+            let vertices = &face_vertices[&face_id];
+            (*line_id, face_id, vertices[line.i0], vertices[line.i1])
+        })
+        .collect::<Vec<_>>();
+
+    let keys = segments
+        .par_iter()
+        .map(|(_, _, v0, v1)| bucket_keys(v0, v1))
+        .collect::<Vec<_>>();
+
+    // Short lines fall outside the bucket tolerances, so are compared against everything
+    let mut buckets = HashMap::<BucketKey, Vec<usize>>::default();
+    let mut unbucketed = vec![];
+    for (i, keys) in keys.iter().enumerate() {
+        match keys {
+            Some(keys) => {
+                for key in keys {
+                    buckets.entry(*key).or_default().push(i);
+                }
+            }
+            None => unbucketed.push(i),
+        }
+    }
+
+    segments
+        .par_iter()
+        .zip(keys.par_iter())
+        .map(|((lhs_id, lhs_face, lhs_v0, lhs_v1), keys)| {
+            let mut candidates = match keys {
+                Some(keys) => keys
+                    .iter()
+                    .flat_map(|key| &buckets[key])
+                    .chain(&unbucketed)
+                    .copied()
+                    .collect::<Vec<_>>(),
+                None => (0..segments.len()).collect(),
+            };
+            candidates.sort_unstable();
+            candidates.dedup();
+
+            let mut connected = BTreeSet::default();
+            connected.insert(*lhs_face);
+
+            for (rhs_id, rhs_face, rhs_v0, rhs_v1) in candidates.into_iter().map(|i| &segments[i]) {
+                if rhs_id != lhs_id && lines_connected(lhs_v0, lhs_v1, rhs_v0, rhs_v1) {
+                    connected.insert(*rhs_face);
+                }
+            }
+
+            (*lhs_id, connected)
+        })
+        .collect()
+}
+
+/// Buckets a segment belongs to, or `None` if it is shorter than `MIN_BUCKET_LENGTH`
+///
+/// Each segment is inserted under both its direction and the reverse,
+/// and under every neighbouring bucket within tolerance,
+/// so any two connected segments share at least one bucket.
+fn bucket_keys(v0: &Vector3, v1: &Vector3) -> Option<Vec<BucketKey>> {
+    let delta = v1 - v0;
+    if delta.magnitude() < MIN_BUCKET_LENGTH {
+        return None;
+    }
+    let direction = delta.normalize();
+
+    let mut keys = vec![];
+    for direction in [direction, -direction] {
+        for cell in direction_cells(&direction) {
+            let axis = nalgebra::vector![cell[0] as f32, cell[1] as f32, cell[2] as f32];
+            let (u, v) = Plane3d {
+                n: axis.normalize(),
+                d: 0.0,
+            }
+            .axes();
+
+            let (x0, y0) = (v0.dot(&u), v0.dot(&v));
+            let (x1, y1) = (v1.dot(&u), v1.dot(&v));
+
+            let cell_range = |lhs: f32, rhs: f32| {
+                let min = ((lhs.min(rhs) - POSITION_TOLERANCE) / POSITION_CELL).floor() as i64;
+                let max = ((lhs.max(rhs) + POSITION_TOLERANCE) / POSITION_CELL).floor() as i64;
+                min..=max
+            };
+
+            for x in cell_range(x0, x1) {
+                for y in cell_range(y0, y1) {
+                    keys.push((cell, [x, y]));
+                }
+            }
+        }
+    }
+
+    keys.sort_unstable();
+    keys.dedup();
+    Some(keys)
+}
+
+/// Direction buckets within tolerance of a normalized direction
+fn direction_cells(direction: &Vector3) -> Vec<[i32; 3]> {
+    let tolerance = DIRECTION_TOLERANCE / DIRECTION_CELL;
+
+    let axis_cells = |component: f32| {
+        let scaled = component / DIRECTION_CELL;
+        let nearest = scaled.round();
+        let offset = scaled - nearest;
+
+        let mut cells = vec![nearest as i32];
+        if offset.abs() > 0.5 - tolerance {
+            cells.push((nearest + offset.signum()) as i32);
+        }
+        cells
+    };
+
+    let xs = axis_cells(direction.x);
+    let ys = axis_cells(direction.y);
+    let zs = axis_cells(direction.z);
+
+    let mut cells = vec![];
+    for x in &xs {
+        for y in &ys {
+            for z in &zs {
+                cells.push([*x, *y, *z]);
+            }
+        }
+    }
+    cells
+}
+
+/// Reference implementation of [`line_face_connections`] comparing every pair of lines
+///
+/// Only available to tests and with the `bench` feature.
+#[cfg(any(test, feature = "bench"))]
+pub fn line_face_connections_exhaustive(
+    lines: &Lines,
+    line_faces: &LineFaces,
+    face_vertices: &FaceVertices,
 ) -> LineFaceConnections {
     let mut line_face_connections = BTreeMap::<LineId, BTreeSet<FaceId>>::default();
 
@@ -51,14 +224,7 @@ pub fn line_face_connections(
             let rhs_v1 = &face_vertices[rhs_face][rhs.i1];
 
             // If the lines are equal, the LHS line connects to the RHS face and vice-versa
-            let lhs_contain_rhs =
-                point_in_line(rhs_v0, lhs_v0, lhs_v1) && point_in_line(rhs_v1, lhs_v0, lhs_v1);
-
-            let rhs_contain_lhs =
-                point_in_line(lhs_v0, rhs_v0, rhs_v1) && point_in_line(lhs_v1, rhs_v0, rhs_v1);
-
-            //let eq = line_eq(lhs_v0, lhs_v1, rhs_v0, rhs_v1);
-            let eq = lhs_contain_rhs || rhs_contain_lhs;
+            let eq = lines_connected(lhs_v0, lhs_v1, rhs_v0, rhs_v1);
 
             if eq {
                 line_face_connections
@@ -76,3 +242,101 @@ pub fn line_face_connections(
 
     LineFaceConnectionsTag::as_usage(line_face_connections)
 }
+
+/// Returns true if either line segment lies along the other
+pub(crate) fn lines_connected(
+    lhs_v0: &Vector3,
+    lhs_v1: &Vector3,
+    rhs_v0: &Vector3,
+    rhs_v1: &Vector3,
+) -> bool {
+    let lhs_contain_rhs =
+        point_in_line(rhs_v0, lhs_v0, lhs_v1) && point_in_line(rhs_v1, lhs_v0, lhs_v1);
+
+    let rhs_contain_lhs =
+        point_in_line(lhs_v0, rhs_v0, rhs_v1) && point_in_line(lhs_v1, rhs_v0, rhs_v1);
+
+    //let eq = line_eq(lhs_v0, lhs_v1, rhs_v0, rhs_v1);
+    lhs_contain_rhs || rhs_contain_lhs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        face::FaceIndices,
+        line::{line_faces, lines},
+    };
+
+    #[test]
+    fn test_matches_exhaustive() {
+        let mut face_vertices = FaceVertices::default();
+        let mut face_indices = FaceIndices::default();
+
+        let mut add_quad = |corners: [Vector3; 4]| {
+            let face_id = FaceId(face_vertices.len());
+            face_vertices.insert(face_id, corners.to_vec());
+            face_indices.insert(face_id, vec![0, 1, 2, 3]);
+            face_id
+        };
+
+        // A large quad bordered by smaller quads along its edges, forming T-junctions
+        let quad = |min: (f32, f32), max: (f32, f32), z: f32| {
+            [
+                nalgebra::vector![min.0, min.1, z],
+                nalgebra::vector![max.0, min.1, z],
+                nalgebra::vector![max.0, max.1, z],
+                nalgebra::vector![min.0, max.1, z],
+            ]
+        };
+
+        add_quad(quad((0.0, 0.0), (64.0, 64.0), 0.0));
+        for i in 0..4 {
+            let x = i as f32 * 16.0;
+            add_quad(quad((x, 64.0), (x + 16.0, 80.0), 0.0));
+            add_quad(quad((x, -16.0), (x + 16.0, 0.0), 0.0));
+        }
+        add_quad(quad((64.0, 0.0), (128.0, 64.0), 0.0));
+        add_quad(quad((0.0, 0.0), (64.0, 64.0), 32.0));
+
+        // Oblique quads sharing an edge
+        let rotation = nalgebra::Rotation3::from_euler_angles(0.3, 0.7, 1.1);
+        for corners in [
+            quad((0.0, 0.0), (40.0, 40.0), 5.0),
+            quad((40.0, 0.0), (80.0, 40.0), 5.0),
+            quad((10.0, 40.0), (30.0, 60.0), 5.0),
+        ] {
+            add_quad(corners.map(|corner| rotation * corner));
+        }
+
+        // Short edges from a shared corner, connected despite directions
+        // that fall either side of a direction bucket boundary
+        let edge = |angle: f32| nalgebra::vector![angle.cos(), angle.sin(), 0.0] * 0.4;
+        let corner = nalgebra::vector![0.0, 200.0, 0.0];
+        let up = nalgebra::vector![0.0, 0.0, 0.4];
+        let short_lhs = add_quad([
+            corner,
+            corner + edge(0.0125),
+            corner + edge(0.0125) + up,
+            corner + up,
+        ]);
+        let short_rhs = add_quad([
+            corner,
+            corner + edge(0.018),
+            corner + edge(0.018) - up,
+            corner - up,
+        ]);
+
+        let (lines, face_lines) = lines(&face_indices);
+        let line_faces = line_faces(&face_lines);
+
+        let expected = line_face_connections_exhaustive(&lines, &line_faces, &face_vertices);
+        let actual = line_face_connections(&lines, &line_faces, &face_vertices);
+
+        assert_eq!(*actual, *expected);
+        assert!(actual.values().any(|faces| faces.len() > 2));
+        assert!(actual
+            .values()
+            .any(|faces| faces.contains(&short_lhs) && faces.contains(&short_rhs)));
+    }
+}
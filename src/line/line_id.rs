@@ -1,4 +1,5 @@
 #[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LineId(pub usize);
 
 impl std::fmt::Display for LineId {
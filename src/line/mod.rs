@@ -19,6 +19,7 @@ use crate::{
 };
 
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Line {
     pub i0: usize,
     pub i1: usize,
@@ -113,14 +114,20 @@ mod tests {
 
     #[test]
     fn test_point_in_line() {
-        let point = Vector3::new(0.0, 0.0, 0.0);
         let v0 = Vector3::new(1.0, 1.0, 1.0);
         let v1 = Vector3::new(-1.0, -1.0, -1.0);
-        let contained = point_in_line(&point, &v0, &v1);
-        println!("Contained: {contained:?}");
 
-        let point = Vector3::new(0.0001, 0.0, 0.0);
-        let contained = point_in_line(&point, &v0, &v1);
-        println!("Contained: {contained:?}");
+        // Endpoints and midpoint
+        assert!(point_in_line(&v0, &v0, &v1));
+        assert!(point_in_line(&v1, &v0, &v1));
+        assert!(point_in_line(&Vector3::new(0.0, 0.0, 0.0), &v0, &v1));
+
+        // Within tolerance of the line
+        assert!(point_in_line(&Vector3::new(0.0001, 0.0, 0.0), &v0, &v1));
+
+        // Off the line, or collinear but beyond either end
+        assert!(!point_in_line(&Vector3::new(0.1, 0.0, 0.0), &v0, &v1));
+        assert!(!point_in_line(&Vector3::new(2.0, 2.0, 2.0), &v0, &v1));
+        assert!(!point_in_line(&Vector3::new(-2.0, -2.0, -2.0), &v0, &v1));
     }
 }
@@ -2,6 +2,7 @@ use crate::{vector3_from_point, Vector3, EPSILON};
 use shalrath::repr::{TexturePlane, TrianglePlane};
 
 #[derive(Debug, Default, Copy, Clone, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Plane3d {
     pub n: Vector3,
     pub d: f32,
@@ -36,6 +37,35 @@ impl Plane3d {
         // Distances should be within EPSILON of one another
         (self.distance().abs() - rhs.distance().abs()).abs() <= EPSILON
     }
+
+    // Returns true if the two planes face the same direction and occupy the same position
+    pub fn coplanar(&self, rhs: &Plane3d) -> bool {
+        self.normal().dot(rhs.normal()) >= 1.0 - EPSILON
+            && (self.distance() - rhs.distance()).abs() <= EPSILON
+    }
+
+    // Returns the plane snapped to an EPSILON grid, for use as a hash key
+    pub fn quantized(&self) -> [i64; 4] {
+        let quantize = |value: f32| (value / EPSILON).round() as i64;
+        [
+            quantize(self.n.x),
+            quantize(self.n.y),
+            quantize(self.n.z),
+            quantize(self.d),
+        ]
+    }
+
+    // Returns a pair of orthonormal axes spanning the plane, derived from the normal alone
+    pub fn axes(&self) -> (Vector3, Vector3) {
+        let reference = if self.n.x.abs() < 0.9 {
+            Vector3::x()
+        } else {
+            Vector3::y()
+        };
+        let u = self.n.cross(&reference).normalize();
+        let v = self.n.cross(&u);
+        (u, v)
+    }
 }
 
 impl From<&TrianglePlane> for Plane3d {
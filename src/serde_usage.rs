@@ -0,0 +1,23 @@
+//! `#[serde(with = "shambler::serde_usage")]` support for [`Usage`]-wrapped tables
+//!
+//! [`Usage`] is defined in another crate, so it cannot implement
+//! `Serialize` and `Deserialize` here; this module serializes the wrapped value in its place.
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use usage::{AsUsage, Usage};
+
+pub fn serialize<U, T, S>(value: &Usage<U, T>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Serialize,
+    S: Serializer,
+{
+    T::serialize(value, serializer)
+}
+
+pub fn deserialize<'de, U, T, D>(deserializer: D) -> Result<Usage<U, T>, D::Error>
+where
+    U: AsUsage,
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    T::deserialize(deserializer).map(U::as_usage)
+}
@@ -0,0 +1,81 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+/// Content-derived identifier that survives reordering of the source map
+///
+/// `hash` is computed from the identifying content of an entity, brush or face,
+/// and `occurrence` distinguishes otherwise identical items in ID order.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StableId {
+    pub hash: u64,
+    pub occurrence: usize,
+}
+
+impl Display for StableId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:016x}", self.hash)?;
+        if self.occurrence > 0 {
+            write!(f, "#{}", self.occurrence)?;
+        }
+        Ok(())
+    }
+}
+
+/// 64-bit FNV-1a, used instead of [`std::hash::Hasher`] implementations
+/// since its output must not change between Rust versions
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct StableHasher(u64);
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        StableHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl StableHasher {
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    pub(crate) fn write_i64(&mut self, value: i64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    pub(crate) fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    pub(crate) fn write_str(&mut self, value: &str) {
+        self.write_u64(value.len() as u64);
+        self.write(value.as_bytes());
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Assign occurrence indices to hashes in ID order
+pub(crate) fn stable_ids<K: Copy + Ord>(
+    hashes: impl IntoIterator<Item = (K, u64)>,
+) -> BTreeMap<K, StableId> {
+    let mut occurrences = BTreeMap::<u64, usize>::default();
+    let mut hashes = hashes.into_iter().collect::<Vec<_>>();
+    hashes.sort_by_key(|(id, _)| *id);
+
+    hashes
+        .into_iter()
+        .map(|(id, hash)| {
+            let occurrence = occurrences.entry(hash).or_default();
+            let stable_id = StableId {
+                hash,
+                occurrence: *occurrence,
+            };
+            *occurrence += 1;
+            (id, stable_id)
+        })
+        .collect()
+}
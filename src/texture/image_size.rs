@@ -0,0 +1,106 @@
+//! Texture size discovery from image file headers
+use std::path::Path;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Number of leading bytes needed to read the size of any supported image
+pub const IMAGE_HEADER_SIZE: usize = 24;
+
+/// Read the dimensions of a PNG image from its IHDR chunk
+pub fn png_size(bytes: &[u8]) -> Option<(u32, u32)> {
+    if !bytes.starts_with(PNG_SIGNATURE) || bytes.get(12..16)? != b"IHDR" {
+        return None;
+    }
+
+    let width = bytes.get(16..20)?;
+    let height = bytes.get(20..24)?;
+
+    Some((
+        u32::from_be_bytes([width[0], width[1], width[2], width[3]]),
+        u32::from_be_bytes([height[0], height[1], height[2], height[3]]),
+    ))
+}
+
+/// Read the dimensions of a TGA image from its header
+pub fn tga_size(bytes: &[u8]) -> Option<(u32, u32)> {
+    // Color-mapped, true-color or grayscale, optionally RLE-compressed
+    match bytes.get(2)? {
+        1 | 2 | 3 | 9 | 10 | 11 => (),
+        _ => return None,
+    }
+
+    let header = bytes.get(12..16)?;
+
+    Some((
+        u16::from_le_bytes([header[0], header[1]]) as u32,
+        u16::from_le_bytes([header[2], header[3]]) as u32,
+    ))
+}
+
+/// Read the dimensions of an image file, dispatching on its extension
+pub fn image_size(path: &Path, bytes: &[u8]) -> Option<(u32, u32)> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    match extension.as_str() {
+        "png" => png_size(bytes),
+        "tga" => tga_size(bytes),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png_header(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = PNG_SIGNATURE.to_vec();
+        bytes.extend_from_slice(&13u32.to_be_bytes());
+        bytes.extend_from_slice(b"IHDR");
+        bytes.extend_from_slice(&width.to_be_bytes());
+        bytes.extend_from_slice(&height.to_be_bytes());
+        bytes
+    }
+
+    fn tga_header(image_type: u8, width: u16, height: u16) -> Vec<u8> {
+        let mut bytes = vec![0; 18];
+        bytes[2] = image_type;
+        bytes[12..14].copy_from_slice(&width.to_le_bytes());
+        bytes[14..16].copy_from_slice(&height.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn test_png_size() {
+        let header = png_header(320, 200);
+        assert_eq!(header.len(), IMAGE_HEADER_SIZE);
+        assert_eq!(png_size(&header), Some((320, 200)));
+
+        // Truncated, wrong signature, or wrong first chunk
+        assert_eq!(png_size(&header[..20]), None);
+        assert_eq!(png_size(&header[1..]), None);
+
+        let mut header = header;
+        header[12..16].copy_from_slice(b"IDAT");
+        assert_eq!(png_size(&header), None);
+    }
+
+    #[test]
+    fn test_tga_size() {
+        assert_eq!(tga_size(&tga_header(2, 64, 32)), Some((64, 32)));
+        assert_eq!(tga_size(&tga_header(10, 512, 256)), Some((512, 256)));
+
+        // Unsupported image type, or truncated
+        assert_eq!(tga_size(&tga_header(0, 64, 32)), None);
+        assert_eq!(tga_size(&tga_header(2, 64, 32)[..14]), None);
+    }
+
+    #[test]
+    fn test_image_size() {
+        let png = png_header(16, 8);
+        let tga = tga_header(2, 16, 8);
+
+        assert_eq!(image_size(Path::new("a/wall.PNG"), &png), Some((16, 8)));
+        assert_eq!(image_size(Path::new("a/wall.tga"), &tga), Some((16, 8)));
+        assert_eq!(image_size(Path::new("a/wall.tga"), &png), None);
+        assert_eq!(image_size(Path::new("a/wall.jpg"), &png), None);
+    }
+}
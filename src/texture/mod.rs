@@ -1,8 +1,15 @@
+mod image_size;
+mod surface_info;
 mod texture_sizes;
+mod wad;
 
+pub use image_size::*;
+pub use surface_info::*;
 pub use texture_sizes::*;
+pub use wad::*;
 
 #[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TextureId(pub usize);
 
 impl std::fmt::Display for TextureId {
@@ -10,4 +17,3 @@ impl std::fmt::Display for TextureId {
         std::fmt::Display::fmt(&self.0, f)
     }
 }
-
@@ -0,0 +1,293 @@
+//! Semantic classification of faces from texture names and Quake 2 surface extensions
+use std::collections::BTreeMap;
+
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use shalrath::repr::Extension;
+use usage::Usage;
+
+use super::TextureId;
+use crate::face::FaceId;
+
+/// Quake 2 surface flags
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SurfaceFlags(pub u32);
+
+impl SurfaceFlags {
+    pub const LIGHT: SurfaceFlags = SurfaceFlags(0x1);
+    pub const SLICK: SurfaceFlags = SurfaceFlags(0x2);
+    pub const SKY: SurfaceFlags = SurfaceFlags(0x4);
+    pub const WARP: SurfaceFlags = SurfaceFlags(0x8);
+    pub const TRANS33: SurfaceFlags = SurfaceFlags(0x10);
+    pub const TRANS66: SurfaceFlags = SurfaceFlags(0x20);
+    pub const FLOWING: SurfaceFlags = SurfaceFlags(0x40);
+    pub const NODRAW: SurfaceFlags = SurfaceFlags(0x80);
+    pub const HINT: SurfaceFlags = SurfaceFlags(0x100);
+    pub const SKIP: SurfaceFlags = SurfaceFlags(0x200);
+
+    pub fn contains(&self, flags: SurfaceFlags) -> bool {
+        self.0 & flags.0 == flags.0
+    }
+
+    pub fn intersects(&self, flags: SurfaceFlags) -> bool {
+        self.0 & flags.0 != 0
+    }
+}
+
+/// Quake 2 content flags
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ContentFlags(pub u32);
+
+impl ContentFlags {
+    pub const SOLID: ContentFlags = ContentFlags(0x1);
+    pub const WINDOW: ContentFlags = ContentFlags(0x2);
+    pub const AUX: ContentFlags = ContentFlags(0x4);
+    pub const LAVA: ContentFlags = ContentFlags(0x8);
+    pub const SLIME: ContentFlags = ContentFlags(0x10);
+    pub const WATER: ContentFlags = ContentFlags(0x20);
+    pub const MIST: ContentFlags = ContentFlags(0x40);
+    pub const AREAPORTAL: ContentFlags = ContentFlags(0x8000);
+    pub const PLAYERCLIP: ContentFlags = ContentFlags(0x10000);
+    pub const MONSTERCLIP: ContentFlags = ContentFlags(0x20000);
+    pub const ORIGIN: ContentFlags = ContentFlags(0x100_0000);
+    pub const DETAIL: ContentFlags = ContentFlags(0x800_0000);
+    pub const TRANSLUCENT: ContentFlags = ContentFlags(0x1000_0000);
+    pub const LADDER: ContentFlags = ContentFlags(0x2000_0000);
+
+    pub const LIQUID: ContentFlags = ContentFlags(Self::LAVA.0 | Self::SLIME.0 | Self::WATER.0);
+    pub const CLIP: ContentFlags = ContentFlags(Self::PLAYERCLIP.0 | Self::MONSTERCLIP.0);
+
+    pub fn contains(&self, flags: ContentFlags) -> bool {
+        self.0 & flags.0 == flags.0
+    }
+
+    pub fn intersects(&self, flags: ContentFlags) -> bool {
+        self.0 & flags.0 != 0
+    }
+}
+
+/// Semantic category of a textured face
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TextureClass {
+    /// Regular visible, solid geometry
+    Solid,
+    /// Invisible collision geometry
+    Clip,
+    /// Faces to be discarded entirely
+    Skip,
+    /// Invisible trigger volume
+    Trigger,
+    /// Sky surface
+    Sky,
+    /// Water, slime, lava or teleporter surface
+    Liquid,
+    /// Visibility hint, discarded from render geometry
+    Hint,
+    /// Pivot point for rotating entities
+    Origin,
+    /// Invisible but solid geometry
+    NoDraw,
+}
+
+impl TextureClass {
+    /// Classify a texture by name
+    ///
+    /// Path prefixes such as `common/` or `e1u1/` are ignored.
+    /// Only the special names used by the Quake-family engines and compilers are recognised,
+    /// so `sky` must be followed by nothing but digits (`sky`, `sky4`),
+    /// and names that merely contain a keyword, such as `paperclip`, stay [`TextureClass::Solid`].
+    pub fn from_name(name: &str) -> TextureClass {
+        let name = name.to_lowercase();
+        let name = name.rsplit(['/', '\\']).next().unwrap_or("");
+
+        if name.starts_with('*') || name.starts_with('!') {
+            TextureClass::Liquid
+        } else if name
+            .strip_prefix("sky")
+            .map_or(false, |suffix| suffix.bytes().all(|b| b.is_ascii_digit()))
+        {
+            TextureClass::Sky
+        } else {
+            match name {
+                "skip" | "hintskip" => TextureClass::Skip,
+                "hint" => TextureClass::Hint,
+                "origin" => TextureClass::Origin,
+                "trigger" | "aaatrigger" => TextureClass::Trigger,
+                "clip" | "playerclip" | "monsterclip" | "weapclip" | "botclip" | "fullclip" => {
+                    TextureClass::Clip
+                }
+                "nodraw" | "caulk" | "null" => TextureClass::NoDraw,
+                _ => TextureClass::Solid,
+            }
+        }
+    }
+
+    /// Returns true if faces of this class belong in render geometry
+    pub fn is_rendered(&self) -> bool {
+        matches!(
+            self,
+            TextureClass::Solid | TextureClass::Sky | TextureClass::Liquid
+        )
+    }
+
+    /// Returns true if faces of this class belong in collision geometry
+    pub fn is_collidable(&self) -> bool {
+        matches!(
+            self,
+            TextureClass::Solid | TextureClass::Sky | TextureClass::Clip | TextureClass::NoDraw
+        )
+    }
+}
+
+/// Decoded surface information for a single face
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SurfaceInfo {
+    pub class: TextureClass,
+    pub content_flags: ContentFlags,
+    pub surface_flags: SurfaceFlags,
+    pub value: f32,
+}
+
+pub enum TextureClassesTag {}
+
+pub type TextureClasses = Usage<TextureClassesTag, BTreeMap<TextureId, TextureClass>>;
+
+pub enum FaceSurfaceInfoTag {}
+
+pub type FaceSurfaceInfo = Usage<FaceSurfaceInfoTag, BTreeMap<FaceId, SurfaceInfo>>;
+
+/// Classify textures by name
+pub fn texture_classes(textures: &BTreeMap<TextureId, String>) -> TextureClasses {
+    textures
+        .par_iter()
+        .map(|(texture_id, texture)| (*texture_id, TextureClass::from_name(texture)))
+        .collect()
+}
+
+/// Decode per-face surface information
+///
+/// Quake 2 surface and content flags take precedence over the name-based texture class.
+pub fn face_surface_info(
+    face_textures: &BTreeMap<FaceId, TextureId>,
+    face_extensions: &BTreeMap<FaceId, Extension>,
+    texture_classes: &TextureClasses,
+) -> FaceSurfaceInfo {
+    face_textures
+        .par_iter()
+        .map(|(face_id, texture_id)| {
+            let texture_class = texture_classes[texture_id];
+
+            let info = match face_extensions.get(face_id) {
+                Some(Extension::Quake2 {
+                    content_flags,
+                    surface_flags,
+                    value,
+                }) => {
+                    let content_flags = ContentFlags(*content_flags);
+                    let surface_flags = SurfaceFlags(*surface_flags);
+                    SurfaceInfo {
+                        class: quake2_class(content_flags, surface_flags).unwrap_or(texture_class),
+                        content_flags,
+                        surface_flags,
+                        value: *value,
+                    }
+                }
+                _ => SurfaceInfo {
+                    class: texture_class,
+                    content_flags: ContentFlags::default(),
+                    surface_flags: SurfaceFlags::default(),
+                    value: 0.0,
+                },
+            };
+
+            (*face_id, info)
+        })
+        .collect()
+}
+
+fn quake2_class(content_flags: ContentFlags, surface_flags: SurfaceFlags) -> Option<TextureClass> {
+    if content_flags.intersects(ContentFlags::ORIGIN) {
+        Some(TextureClass::Origin)
+    } else if surface_flags.intersects(SurfaceFlags::SKIP) {
+        Some(TextureClass::Skip)
+    } else if surface_flags.intersects(SurfaceFlags::HINT) {
+        Some(TextureClass::Hint)
+    } else if surface_flags.intersects(SurfaceFlags::SKY) {
+        Some(TextureClass::Sky)
+    } else if content_flags.intersects(ContentFlags::CLIP) {
+        Some(TextureClass::Clip)
+    } else if content_flags.intersects(ContentFlags::LIQUID)
+        || surface_flags.intersects(SurfaceFlags::WARP)
+    {
+        Some(TextureClass::Liquid)
+    } else if surface_flags.intersects(SurfaceFlags::NODRAW) {
+        Some(TextureClass::NoDraw)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_name() {
+        let cases = [
+            ("*water1", TextureClass::Liquid),
+            ("!toxic", TextureClass::Liquid),
+            ("sky", TextureClass::Sky),
+            ("e1u1/SKY4", TextureClass::Sky),
+            ("skylight_panel", TextureClass::Solid),
+            ("skip", TextureClass::Skip),
+            ("common/hintskip", TextureClass::Skip),
+            ("hint", TextureClass::Hint),
+            ("hinterland", TextureClass::Solid),
+            ("origin", TextureClass::Origin),
+            ("AAATRIGGER", TextureClass::Trigger),
+            ("trigger", TextureClass::Trigger),
+            ("common\\clip", TextureClass::Clip),
+            ("playerclip", TextureClass::Clip),
+            ("monsterclip", TextureClass::Clip),
+            ("paperclip", TextureClass::Solid),
+            ("clipboard", TextureClass::Solid),
+            ("caulk", TextureClass::NoDraw),
+            ("nodraw", TextureClass::NoDraw),
+            ("null", TextureClass::NoDraw),
+            ("wall1", TextureClass::Solid),
+        ];
+
+        for (name, class) in cases {
+            assert_eq!(TextureClass::from_name(name), class, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_quake2_class() {
+        let none = ContentFlags::default();
+        let no_surface = SurfaceFlags::default();
+
+        assert_eq!(quake2_class(none, no_surface), None);
+        assert_eq!(quake2_class(ContentFlags::SOLID, SurfaceFlags::LIGHT), None);
+
+        assert_eq!(
+            quake2_class(ContentFlags::ORIGIN, SurfaceFlags::SKY),
+            Some(TextureClass::Origin)
+        );
+        assert_eq!(
+            quake2_class(none, SurfaceFlags(SurfaceFlags::SKIP.0 | SurfaceFlags::HINT.0)),
+            Some(TextureClass::Skip)
+        );
+        assert_eq!(quake2_class(none, SurfaceFlags::HINT), Some(TextureClass::Hint));
+        assert_eq!(
+            quake2_class(ContentFlags::PLAYERCLIP, SurfaceFlags::SKY),
+            Some(TextureClass::Sky)
+        );
+        assert_eq!(quake2_class(ContentFlags::MONSTERCLIP, no_surface), Some(TextureClass::Clip));
+        assert_eq!(quake2_class(ContentFlags::SLIME, no_surface), Some(TextureClass::Liquid));
+        assert_eq!(quake2_class(none, SurfaceFlags::WARP), Some(TextureClass::Liquid));
+        assert_eq!(
+            quake2_class(ContentFlags::SOLID, SurfaceFlags::NODRAW),
+            Some(TextureClass::NoDraw)
+        );
+    }
+}
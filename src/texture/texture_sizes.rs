@@ -1,26 +1,210 @@
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
-use std::collections::BTreeMap;
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{Read, Result},
+    path::Path,
+};
 use usage::Usage;
 
-use super::TextureId;
+use super::{image_size, wad_texture_sizes, TextureId, IMAGE_HEADER_SIZE};
 
 pub enum TextureSizesTag {}
 
 pub type TextureSizes = Usage<TextureSizesTag, BTreeMap<TextureId, (u32, u32)>>;
 
 /// Construct using a name -> size map
+///
+/// Names are matched exactly first, then case-insensitively,
+/// then against any frame of the same animated texture.
 pub fn texture_sizes(
     textures: &BTreeMap<TextureId, String>,
     texture_sizes: BTreeMap<&str, (u32, u32)>,
 ) -> TextureSizes {
+    let normalized = texture_sizes
+        .iter()
+        .map(|(name, size)| (texture_size_key(name), *size))
+        .collect::<BTreeMap<_, _>>();
+
     textures
         .par_iter()
         .flat_map(|(texture_id, texture)| {
-            if let Some(texture_size) = texture_sizes.get(texture.as_str()) {
-                Some((*texture_id, *texture_size))
-            } else {
-                None
-            }
+            texture_sizes
+                .get(texture.as_str())
+                .copied()
+                .or_else(|| find_texture_size(&normalized, texture))
+                .map(|texture_size| (*texture_id, texture_size))
         })
         .collect()
 }
+
+/// Construct by reading WAD2 / WAD3 archives and PNG / TGA images from a directory
+pub fn texture_sizes_from_dir(
+    textures: &BTreeMap<TextureId, String>,
+    path: impl AsRef<Path>,
+) -> Result<TextureSizes> {
+    let sizes = texture_size_map_from_dir(path)?;
+
+    Ok(textures
+        .par_iter()
+        .flat_map(|(texture_id, texture)| {
+            find_texture_size(&sizes, texture).map(|texture_size| (*texture_id, texture_size))
+        })
+        .collect())
+}
+
+/// Recursively scan a directory for texture files, returning a normalized name -> size map
+///
+/// Images are keyed by both their path relative to `path` and their file stem,
+/// so that `base/wall1.png` will match either `base/wall1` or `wall1`.
+///
+/// Files that can't be parsed, such as Doom IWADs or truncated images, are skipped.
+pub fn texture_size_map_from_dir(path: impl AsRef<Path>) -> Result<BTreeMap<String, (u32, u32)>> {
+    let root = path.as_ref();
+
+    let mut sizes = BTreeMap::default();
+    let mut image_stems = BTreeMap::default();
+    let mut dirs = vec![root.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+
+            if path.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+
+            let extension = match path.extension().and_then(|ext| ext.to_str()) {
+                Some(extension) => extension.to_lowercase(),
+                None => continue,
+            };
+
+            if extension == "wad" {
+                if let Ok(wad_sizes) = wad_texture_sizes(&fs::read(&path)?) {
+                    for (name, size) in wad_sizes {
+                        sizes.insert(texture_size_key(&name), size);
+                    }
+                }
+                continue;
+            }
+
+            if extension != "png" && extension != "tga" {
+                continue;
+            }
+
+            // Only the header is needed for dimensions
+            let mut header = Vec::with_capacity(IMAGE_HEADER_SIZE);
+            File::open(&path)?
+                .take(IMAGE_HEADER_SIZE as u64)
+                .read_to_end(&mut header)?;
+
+            let size = match image_size(&path, &header) {
+                Some(size) => size,
+                None => continue,
+            };
+
+            let relative = path.strip_prefix(root).unwrap_or(&path).with_extension("");
+            let relative = relative
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            sizes.insert(texture_size_key(&relative), size);
+
+            if let Some(stem) = path.file_stem() {
+                image_stems.insert(texture_size_key(&stem.to_string_lossy()), size);
+            }
+        }
+    }
+
+    // Stems only fill gaps left by WAD entries and relative paths
+    for (stem, size) in image_stems {
+        sizes.entry(stem).or_insert(size);
+    }
+
+    Ok(sizes)
+}
+
+/// Normalize a texture name for size lookup
+///
+/// Lowercases the name, converts path separators to `/`,
+/// and maps the filesystem-safe `#` liquid prefix back to `*`.
+pub fn texture_size_key(name: &str) -> String {
+    let name = name.to_lowercase().replace('\\', "/");
+    match name.strip_prefix('#') {
+        Some(liquid) => format!("*{}", liquid),
+        None => name,
+    }
+}
+
+/// Look up a texture in a normalized name -> size map
+///
+/// Animated `+<frame>name` textures fall back to any other frame of the same sequence,
+/// since every frame of an animation shares the same dimensions.
+pub fn find_texture_size(sizes: &BTreeMap<String, (u32, u32)>, name: &str) -> Option<(u32, u32)> {
+    let key = texture_size_key(name);
+
+    if let Some(size) = sizes.get(&key) {
+        return Some(*size);
+    }
+
+    let base = animated_texture_base(&key)?;
+    sizes
+        .iter()
+        .find(|(candidate, _)| animated_texture_base(candidate) == Some(base))
+        .map(|(_, size)| *size)
+}
+
+fn animated_texture_base(name: &str) -> Option<&str> {
+    let bytes = name.as_bytes();
+    if bytes.len() > 2 && bytes[0] == b'+' && bytes[1].is_ascii_alphanumeric() {
+        Some(&name[2..])
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sizes(entries: &[(&str, (u32, u32))]) -> BTreeMap<String, (u32, u32)> {
+        entries
+            .iter()
+            .map(|(name, size)| (texture_size_key(name), *size))
+            .collect()
+    }
+
+    #[test]
+    fn test_texture_size_key() {
+        assert_eq!(texture_size_key("Base\\WALL1"), "base/wall1");
+        assert_eq!(texture_size_key("#Water1"), "*water1");
+        assert_eq!(texture_size_key("*lava"), "*lava");
+    }
+
+    #[test]
+    fn test_find_texture_size() {
+        let sizes = sizes(&[
+            ("Wall1", (64, 64)),
+            ("#slime", (128, 128)),
+            ("+0button", (32, 16)),
+        ]);
+
+        // Case-insensitive
+        assert_eq!(find_texture_size(&sizes, "WALL1"), Some((64, 64)));
+        assert_eq!(find_texture_size(&sizes, "wall1"), Some((64, 64)));
+
+        // `#` on disk matches `*` in the map, and vice versa
+        assert_eq!(find_texture_size(&sizes, "*slime"), Some((128, 128)));
+        assert_eq!(find_texture_size(&sizes, "#SLIME"), Some((128, 128)));
+
+        // Any frame of an animation matches any other
+        assert_eq!(find_texture_size(&sizes, "+3button"), Some((32, 16)));
+        assert_eq!(find_texture_size(&sizes, "+aBUTTON"), Some((32, 16)));
+
+        assert_eq!(find_texture_size(&sizes, "button"), None);
+        assert_eq!(find_texture_size(&sizes, "+1wall"), None);
+        assert_eq!(find_texture_size(&sizes, "wall2"), None);
+    }
+}
@@ -0,0 +1,119 @@
+//! Texture size discovery from Quake WAD2 and Half-Life WAD3 archives
+use std::{
+    collections::BTreeMap,
+    io::{Error, ErrorKind, Result},
+};
+
+const WAD_HEADER_SIZE: usize = 12;
+const WAD_ENTRY_SIZE: usize = 32;
+const WAD_NAME_SIZE: usize = 16;
+
+const LUMP_TYPE_MIPTEX_WAD2: u8 = 0x44;
+const LUMP_TYPE_MIPTEX_WAD3: u8 = 0x43;
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| Error::new(ErrorKind::UnexpectedEof, "WAD data truncated"))
+}
+
+fn read_name(bytes: &[u8], offset: usize) -> Result<String> {
+    let name = bytes
+        .get(offset..offset + WAD_NAME_SIZE)
+        .ok_or_else(|| Error::new(ErrorKind::UnexpectedEof, "WAD data truncated"))?;
+    let len = name.iter().position(|b| *b == 0).unwrap_or(WAD_NAME_SIZE);
+    Ok(String::from_utf8_lossy(&name[..len]).into_owned())
+}
+
+/// Read the name and size of each miptex lump in a WAD2 or WAD3 archive
+pub fn wad_texture_sizes(bytes: &[u8]) -> Result<BTreeMap<String, (u32, u32)>> {
+    let miptex_type = match bytes.get(0..4) {
+        Some(b"WAD2") => LUMP_TYPE_MIPTEX_WAD2,
+        Some(b"WAD3") => LUMP_TYPE_MIPTEX_WAD3,
+        _ => {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Not a WAD2 or WAD3 file",
+            ))
+        }
+    };
+
+    if bytes.len() < WAD_HEADER_SIZE {
+        return Err(Error::new(ErrorKind::UnexpectedEof, "WAD header truncated"));
+    }
+
+    let lump_count = read_u32(bytes, 4)? as usize;
+    let directory_offset = read_u32(bytes, 8)? as usize;
+
+    let mut sizes = BTreeMap::default();
+
+    for i in 0..lump_count {
+        let entry = directory_offset + i * WAD_ENTRY_SIZE;
+
+        let lump_offset = read_u32(bytes, entry)? as usize;
+        let lump_type = *bytes
+            .get(entry + 12)
+            .ok_or_else(|| Error::new(ErrorKind::UnexpectedEof, "WAD directory truncated"))?;
+
+        // Skip palettes, status bar pics and other non-texture lumps
+        if lump_type != miptex_type {
+            continue;
+        }
+
+        let name = read_name(bytes, entry + 16)?;
+
+        // Miptex header: name[16], width, height, mip offsets[4]
+        let width = read_u32(bytes, lump_offset + WAD_NAME_SIZE)?;
+        let height = read_u32(bytes, lump_offset + WAD_NAME_SIZE + 4)?;
+
+        sizes.insert(name, (width, height));
+    }
+
+    Ok(sizes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wad(magic: &[u8; 4], lump_type: u8, name: &str, width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = magic.to_vec();
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bytes.extend_from_slice(&(WAD_HEADER_SIZE as u32 + 40).to_le_bytes());
+
+        let mut miptex_name = [0u8; WAD_NAME_SIZE];
+        miptex_name[..name.len()].copy_from_slice(name.as_bytes());
+
+        // Miptex lump
+        bytes.extend_from_slice(&miptex_name);
+        bytes.extend_from_slice(&width.to_le_bytes());
+        bytes.extend_from_slice(&height.to_le_bytes());
+        bytes.extend_from_slice(&[0u8; 16]);
+
+        // Directory entry
+        bytes.extend_from_slice(&(WAD_HEADER_SIZE as u32).to_le_bytes());
+        bytes.extend_from_slice(&40u32.to_le_bytes());
+        bytes.extend_from_slice(&40u32.to_le_bytes());
+        bytes.extend_from_slice(&[lump_type, 0, 0, 0]);
+        bytes.extend_from_slice(&miptex_name);
+
+        bytes
+    }
+
+    #[test]
+    fn test_wad_texture_sizes() {
+        let sizes =
+            wad_texture_sizes(&wad(b"WAD2", LUMP_TYPE_MIPTEX_WAD2, "*water1", 64, 32)).unwrap();
+        assert_eq!(sizes.get("*water1"), Some(&(64, 32)));
+
+        let sizes =
+            wad_texture_sizes(&wad(b"WAD3", LUMP_TYPE_MIPTEX_WAD3, "+0button", 128, 16)).unwrap();
+        assert_eq!(sizes.get("+0button"), Some(&(128, 16)));
+
+        let sizes = wad_texture_sizes(&wad(b"WAD2", 0x42, "conback", 320, 200)).unwrap();
+        assert!(sizes.is_empty());
+
+        assert!(wad_texture_sizes(b"PACK").is_err());
+    }
+}